@@ -0,0 +1,10 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Message sent over the channel passed to `download_update`.
+ */
+export type DownloadEvent = { "event": "started", "data": { total: number | null, resumedFrom: number, } } | { "event": "progress", "data": { downloaded: number, total: number | null, bytesPerSec: number, 
+/**
+ * Seconds left at the current rate.
+ */
+eta: number | null, } } | { "event": "finished" } | { "event": "failed", "data": { error: string, } } | { "event": "cancelled" };
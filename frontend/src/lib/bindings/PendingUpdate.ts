@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * A verified update waiting to be installed. Kept on disk so it survives
+ * restarts until it is installed or superseded.
+ */
+export type PendingUpdate = { version: string, 
+/**
+ * Install when the app quits instead of waiting for the user.
+ */
+installOnExit: boolean, };
@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Release channel the updater follows. Each has its own manifest next to
+ * `latest.json`, e.g. `latest-beta.json`.
+ */
+export type UpdateChannel = "stable" | "beta" | "nightly";
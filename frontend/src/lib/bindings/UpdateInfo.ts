@@ -0,0 +1,18 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * An available update, returned by `check_for_updates`.
+ */
+export type UpdateInfo = { version: string, currentVersion: string, 
+/**
+ * Release date from `latest.json`, RFC 3339.
+ */
+date: string | null, 
+/**
+ * Release notes.
+ */
+body: string | null, downloadUrl: string, 
+/**
+ * Installer size in bytes, if the server reports it.
+ */
+size: number | null, };
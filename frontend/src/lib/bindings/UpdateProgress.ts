@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Snapshot of the running download, returned by `get_update_progress` for
+ * callers that missed the channel messages.
+ */
+export type UpdateProgress = { downloading: boolean, percent: number, downloaded: number, total: number | null, bytesPerSec: number, 
+/**
+ * Seconds left at the current rate.
+ */
+eta: number | null, complete: boolean, cancelled: boolean, error: string | null, };
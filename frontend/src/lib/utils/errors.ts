@@ -0,0 +1,38 @@
+// Mirrors `AppError` in src-tauri/src/error.rs
+export interface AppError {
+  kind:
+    | 'RNotFound'
+    | 'AppsNotFound'
+    | 'UnknownApp'
+    | 'AlreadyRunning'
+    | 'NotRunning'
+    | 'UnknownOperation'
+    | 'Cancelled'
+    | 'NetworkRefused'
+    | 'PortExhausted'
+    | 'StartupTimeout'
+    | 'StartupFailed'
+    | 'NoUpdateAvailable'
+    | 'UpdateInProgress'
+    | 'NoUpdateDownload'
+    | 'NoRollbackAvailable'
+    | 'UpdateSignatureInvalid'
+    | 'UpdateChecksumMismatch'
+    | 'NetworkUnavailable'
+    | 'UpdateFailed'
+    | 'Internal';
+  message: string;
+  details: string | null;
+}
+
+export function isAppError(error: unknown): error is AppError {
+  return typeof error === 'object' && error !== null && 'kind' in error && 'message' in error;
+}
+
+// User-facing text for anything a command rejected with
+export function errorMessage(error: unknown): string {
+  if (isAppError(error)) {
+    return error.details ? `${error.message}: ${error.details}` : error.message;
+  }
+  return String(error);
+}
@@ -1,18 +1,135 @@
 import { invoke } from '@tauri-apps/api/core';
+import { listen } from '@tauri-apps/api/event';
+import type { ShinySession } from './shinyListener';
+import { isAppError, type AppError } from './errors';
 
-export async function launchShinyApp() {
+export interface ShinyAppInfo {
+    id: string;
+    path: string;
+    status: ShinySession;
+}
+
+export async function listShinyApps(): Promise<ShinyAppInfo[]> {
+    try {
+        return await invoke<ShinyAppInfo[]>('list_shiny_apps');
+    } catch (error) {
+        console.error("Failed to list Shiny apps:", error);
+        return [];
+    }
+}
+
+interface StartResult {
+    opId: string;
+    appId: string;
+    url: string;
+}
+
+// `shiny-error` carries the `AppError` fields alongside the ids
+type StartError = AppError & { opId: string; appId: string };
+
+type StartOutcome = { url: string } | { error: AppError };
+
+// Listens for start outcomes before `start_r_shiny` is invoked: one can be
+// emitted before the invoke returns its id, e.g. for an app that is already
+// running. `wait` resolves with the URL for one id; call `stop` when done.
+async function listenForStart() {
+    const early = new Map<string, StartOutcome>();
+    const waiters = new Map<string, (outcome: StartOutcome) => void>();
+    const settle = (opId: string, outcome: StartOutcome) => {
+        const waiter = waiters.get(opId);
+        if (waiter) waiter(outcome);
+        else early.set(opId, outcome);
+    };
+    const unlisteners = await Promise.all([
+        listen<StartResult>('shiny-started', (event) => {
+            settle(event.payload.opId, { url: event.payload.url });
+        }),
+        listen<StartError>('shiny-error', (event) => {
+            const { opId, kind, message, details } = event.payload;
+            settle(opId, { error: { kind, message, details } });
+        })
+    ]);
+    const wait = (opId: string) =>
+        new Promise<string>((resolve, reject) => {
+            const finish = (outcome: StartOutcome) =>
+                'url' in outcome ? resolve(outcome.url) : reject(outcome.error);
+            const outcome = early.get(opId);
+            if (outcome) finish(outcome);
+            else waiters.set(opId, finish);
+        });
+    const stop = () => unlisteners.forEach((unlisten) => unlisten());
+    return { wait, stop };
+}
+
+export async function launchShinyApp(appId: string, onStart?: (opId: string) => void) {
+    const outcomes = await listenForStart();
     try {
-        const url = await invoke<string>('start_r_shiny');
-        window.open(url, '_blank');
+        const opId = await invoke<string>('start_r_shiny', { appId });
+        onStart?.(opId);
+        await outcomes.wait(opId);
+        await openShinyWindow(appId);
     } catch (error) {
-        console.error("Failed to launch R Shiny app:", error);
+        if (isAppError(error) && error.kind === 'Cancelled') return;
+        console.error(`Failed to launch R Shiny app '${appId}':`, error);
+    } finally {
+        outcomes.stop();
     }
 }
 
-export async function stopShinyApp() {
+// Opens the app in its own window, or focuses it if already open
+export async function openShinyWindow(appId: string) {
+    await invoke('open_shiny_window', { appId });
+}
+
+// URL of an app under the `shiny` URI scheme (src-tauri/src/shiny_scheme.rs),
+// for embedding in the main window. Windows serves custom schemes as
+// http://<scheme>.localhost.
+export function shinyEmbedUrl(appId: string): string {
+    const base = navigator.userAgent.includes('Windows')
+        ? 'http://shiny.localhost'
+        : 'shiny://localhost';
+    return `${base}/${encodeURIComponent(appId)}/`;
+}
+
+export async function cancelShinyStart(opId: string) {
+    try {
+        await invoke('cancel_shiny_start', { opId });
+    } catch (error) {
+        console.error("Failed to cancel Shiny start:", error);
+    }
+}
+
+// Mirrors `LogLine` in src-tauri/src/shiny_log.rs
+export interface ShinyLogLine {
+    seq: number;
+    appId: string;
+    stream: 'stdout' | 'stderr';
+    timestamp: number;
+    line: string;
+}
+
+// Buffered R output newer than `since` (the last `seq` already shown)
+export async function getShinyLogs(appId: string, since?: number): Promise<ShinyLogLine[]> {
+    try {
+        return await invoke<ShinyLogLine[]>('get_shiny_logs', { appId, since });
+    } catch (error) {
+        console.error("Failed to get Shiny logs:", error);
+        return [];
+    }
+}
+
+// Mirrors `StopReport` in src-tauri/src/shiny_process.rs
+export interface StopReport {
+    outcome: 'alreadyExited' | 'terminated' | 'killed';
+    exitCode: number | null;
+    groupKilled: boolean;
+    elapsedMs: number;
+}
+
+export async function stopShinyApp(appId: string) {
     try {
-        await invoke('stop_r_shiny');
-        console.log("R Shiny app stopped.");
+        const report = await invoke<StopReport>('stop_r_shiny', { appId });
+        console.log(`R Shiny app '${appId}' stopped:`, report);
     } catch (error) {
         console.error("Failed to stop R:", error);
     }
@@ -1,36 +1,117 @@
 import { listen } from '@tauri-apps/api/event'
 import { writable, type Writable } from 'svelte/store'
+import { listShinyApps, type ShinyLogLine } from './shiny'
+import { errorMessage, type AppError } from './errors'
 
 // Define types for better type safety
 type ShinyStatus = 'idle' | 'running' | 'stopped' | 'error' | string;
 
-// Create Svelte stores with proper typing
-export const shinyStatus: Writable<ShinyStatus> = writable('idle')
-export const shinyUrl = writable('')
-export const shinyError = writable<string | null>(null)
+// Mirrors `ShinyState` in src-tauri/src/shiny_manager.rs
+export type ShinyState =
+  | { state: 'idle' }
+  | { state: 'starting' }
+  | { state: 'ready' }
+  | { state: 'unhealthy' }
+  | { state: 'stopping' }
+  | { state: 'exited'; code: number | null }
+  | { state: 'crashed' }
+
+export type ShinySession = ShinyState & {
+  appId: string
+  port: number | null
+  url: string | null
+  pid: number | null
+  startedAt: number | null
+  exitCode: number | null
+  lastStderr: string[]
+  restarts: number
+}
+
+interface ShinyTransition {
+  from: ShinyState
+  status: ShinySession
+}
+
+// Several apps can run at once, so these are keyed by app id
+export const shinyStatus: Writable<Record<string, ShinyStatus>> = writable({})
+export const shinyUrl = writable<Record<string, string>>({})
+export const shinyError = writable<Record<string, string>>({})
+export const shinySession = writable<Record<string, ShinySession>>({})
+// Live R output, capped like the backend buffer
+const MAX_LOG_LINES = 2000
+export const shinyLogs = writable<ShinyLogLine[]>([])
+// Set while an app is reachable from other machines
+export const shinyLanWarning = writable<string | null>(null)
+
+// Sets `appId`'s entry in a keyed store
+function setFor<T>(store: Writable<Record<string, T>>, appId: string, value: T) {
+  store.update((entries) => ({ ...entries, [appId]: value }))
+}
+
+// Removes `appId`'s entry from a keyed store
+function clearFor<T>(store: Writable<Record<string, T>>, appId: string) {
+  store.update((entries) => {
+    const rest = { ...entries }
+    delete rest[appId]
+    return rest
+  })
+}
 
 // Function to initialize listeners
 export function initShinyListeners() {
-  listen('shiny-status', (event) => {
-    console.log('Shiny status:', event.payload)
-    shinyStatus.set(event.payload as string)
+  // Events that arrived meanwhile are newer than the listing
+  listShinyApps().then((apps) =>
+    shinySession.update((sessions) => ({
+      ...Object.fromEntries(apps.map((app) => [app.id, app.status])),
+      ...sessions
+    }))
+  )
+
+  listen<ShinyTransition>('shiny-state-changed', (event) => {
+    console.log('Shiny state:', event.payload.from.state, '->', event.payload.status.state)
+    setFor(shinySession, event.payload.status.appId, event.payload.status)
+  })
+
+  listen<{ opId: string; appId: string; message: string }>('shiny-start-progress', (event) => {
+    console.log(`Shiny start ${event.payload.opId}:`, event.payload.message)
+    setFor(shinyStatus, event.payload.appId, event.payload.message)
   })
   
-  listen('shiny-started', (event) => {
-    console.log('Shiny started at:', event.payload)
-    shinyUrl.set(event.payload as string)
-    shinyStatus.set('running')
+  listen<{ opId: string; appId: string; url: string }>('shiny-started', (event) => {
+    console.log('Shiny started at:', event.payload.url)
+    setFor(shinyUrl, event.payload.appId, event.payload.url)
+    setFor(shinyStatus, event.payload.appId, 'running')
+    clearFor(shinyError, event.payload.appId)
   })
   
-  listen('shiny-stopped', () => {
-    console.log('Shiny stopped')
-    shinyStatus.set('stopped')
-    shinyUrl.set('')
+  listen<{ appId: string; url: string; attempt: number; previousExitCode: number | null }>(
+    'shiny-restarted',
+    (event) => {
+      console.log(`Shiny restarted (attempt ${event.payload.attempt}) at:`, event.payload.url)
+      setFor(shinyUrl, event.payload.appId, event.payload.url)
+      setFor(shinyStatus, event.payload.appId, 'running')
+    }
+  )
+
+  listen<{ appId: string; address: string; port: number }>('shiny-lan-sharing', (event) => {
+    const { appId, address, port } = event.payload
+    console.warn(`Shiny app ${appId} is shared on the network at ${address}:${port}`)
+    shinyLanWarning.set(`${appId} is reachable from the network at ${address}:${port}`)
+  })
+
+  listen<ShinyLogLine>('shiny-log', (event) => {
+    shinyLogs.update((lines) => [...lines, event.payload].slice(-MAX_LOG_LINES))
+  })
+
+  listen<string>('shiny-stopped', (event) => {
+    console.log('Shiny stopped:', event.payload)
+    setFor(shinyStatus, event.payload, 'stopped')
+    clearFor(shinyUrl, event.payload)
   })
   
-  listen('shiny-error', (event) => {
+  listen<AppError & { opId: string; appId: string }>('shiny-error', (event) => {
     console.error('Shiny error:', event.payload)
-    shinyError.set(event.payload as string)
-    shinyStatus.set('error')
+    setFor(shinyError, event.payload.appId, errorMessage(event.payload))
+    setFor(shinyStatus, event.payload.appId, 'error')
   })
 }
\ No newline at end of file
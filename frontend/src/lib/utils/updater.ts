@@ -1,41 +1,170 @@
-import { invoke } from '@tauri-apps/api/core';
+import { Channel, invoke } from '@tauri-apps/api/core';
 import { relaunch } from '@tauri-apps/plugin-process';
 import { message, ask } from '@tauri-apps/plugin-dialog';
 import { get, writable } from 'svelte/store';
-
-// Interface for the update info returned by Rust
-interface UpdateInfo {
-  available: boolean;
-  version?: string;
-  body?: string;
-  date?: string;
-  downloadUrl?: string;
-}
-
-// Interface for the progress info
-interface UpdateProgress {
-  downloading: boolean;
-  percent: number;
-  downloaded: number;
-  total?: number;
-  complete: boolean;
-  error?: string;
-}
+import { errorMessage, isAppError } from './errors';
+import type { DownloadEvent } from '../bindings/DownloadEvent';
+import type { PendingUpdate } from '../bindings/PendingUpdate';
+import type { UpdateChannel } from '../bindings/UpdateChannel';
+import type { UpdateInfo } from '../bindings/UpdateInfo';
+import type { UpdateProgress } from '../bindings/UpdateProgress';
 
 // Create a store for managing the progress dialog visibility
 export const updateProgressVisible = writable(false);
 export const updateComplete = writable(false);
 export const updateError = writable<string | null>(null);
+// Latest download progress, fed by the channel passed to the download command
+export const updateProgress = writable<UpdateProgress | null>(null);
+
+function downloadChannel(): Channel<DownloadEvent> {
+  const channel = new Channel<DownloadEvent>();
+  channel.onmessage = (message) => {
+    switch (message.event) {
+      case 'started': {
+        const { total, resumedFrom } = message.data;
+        updateProgress.set({
+          downloading: true,
+          percent: total ? (resumedFrom / total) * 100 : 0,
+          downloaded: resumedFrom,
+          total,
+          bytesPerSec: 0,
+          eta: null,
+          complete: false,
+          cancelled: false,
+          error: null
+        });
+        break;
+      }
+      case 'progress': {
+        const { downloaded, total, bytesPerSec, eta } = message.data;
+        updateProgress.update((p) => p && {
+          ...p,
+          downloaded,
+          total,
+          bytesPerSec,
+          eta,
+          percent: total ? (downloaded / total) * 100 : p.percent
+        });
+        break;
+      }
+      case 'finished':
+        updateProgress.update((p) => p && { ...p, percent: 100, eta: 0, complete: true });
+        updateComplete.set(true);
+        break;
+      case 'cancelled':
+        updateProgress.update((p) => p && { ...p, downloading: false, cancelled: true });
+        break;
+      case 'failed':
+        updateProgress.update((p) => p && { ...p, downloading: false, error: message.data.error });
+        updateError.set(message.data.error);
+        break;
+    }
+  };
+  return channel;
+}
+
+// Stops the running download; `download_update` then rejects with `Cancelled`
+export async function cancelUpdateDownload() {
+  try {
+    await invoke('cancel_update_download');
+  } catch (error) {
+    console.error('Failed to cancel update download:', error);
+  }
+}
+
+export async function getUpdateChannel(): Promise<UpdateChannel> {
+  return invoke<UpdateChannel>('get_update_channel');
+}
+
+// Identifies this install in a release's `allowlist`
+export async function getInstallId(): Promise<string> {
+  return invoke<string>('get_install_id');
+}
+
+// Switching drops an update downloaded from the previous channel
+export async function setUpdateChannel(channel: UpdateChannel) {
+  try {
+    await invoke('set_update_channel', { channel });
+  } catch (error) {
+    console.error('Failed to set update channel:', error);
+    await message(`Could not change the update channel: ${errorMessage(error)}`, {
+      title: 'Update Error'
+    });
+  }
+}
+
+// Lets the user restart into a downloaded update now or have it installed
+// when they quit, so a session in progress is not interrupted
+async function promptInstall(pending: PendingUpdate) {
+  const installNow = await ask(
+    `Version ${pending.version} has been downloaded.\n\n` +
+    'Restart now to install it, or install it the next time you quit?',
+    {
+      title: 'Update Ready',
+      okLabel: 'Restart now',
+      cancelLabel: 'When I quit'
+    }
+  );
+  if (installNow) {
+    await invoke('install_downloaded_update');
+    await relaunch();
+  } else {
+    await invoke<PendingUpdate>('install_on_exit', { enabled: true });
+  }
+}
+
+// Tells the backend this version launched fine, ending its probation after
+// an update; until then a version that keeps failing is rolled back
+export async function markUpdateHealthy() {
+  try {
+    await invoke('mark_update_healthy');
+  } catch (error) {
+    console.error('Failed to mark update healthy:', error);
+  }
+}
+
+export async function getRollbackVersion(): Promise<string | null> {
+  return invoke<string | null>('get_rollback_version');
+}
+
+// Reinstalls the version the last update replaced
+export async function rollbackUpdate(version: string) {
+  const confirmed = await ask(
+    `Reinstall version ${version}? The current version will not be offered again.`,
+    {
+      title: 'Roll Back Update',
+      okLabel: 'Roll back',
+      cancelLabel: 'Cancel'
+    }
+  );
+  if (!confirmed) return;
+  try {
+    // Restarts into the previous version on success
+    await invoke('rollback_update');
+  } catch (error) {
+    console.error('Rollback failed:', error);
+    await message(`Failed to roll back: ${errorMessage(error)}`, {
+      title: 'Update Error'
+    });
+  }
+}
 
 export async function checkForUpdates() {
   try {
+    // An update downloaded earlier only needs installing
+    const pending = await invoke<PendingUpdate | null>('get_pending_update');
+    if (pending) {
+      await promptInstall(pending);
+      return;
+    }
+
+
     // Check for updates using the Rust command
-    const response = await invoke<string>('check_for_updates');
-    const updateInfo: UpdateInfo = JSON.parse(response);
+    const updateInfo = await invoke<UpdateInfo | null>('check_for_updates');
     
     console.log('Update check result:', updateInfo);
     
-    if (!updateInfo.available) {
+    if (!updateInfo) {
       console.log('No update available');
       await message('You are running the latest version.', {
         title: 'No Update Available'
@@ -45,13 +174,16 @@ export async function checkForUpdates() {
     
     // Show confirmation dialog
     const shouldUpdate = await ask(
-      `A new version (${updateInfo.version}) is available.\n\n` +
+      `A new version (${updateInfo.version}) is available. ` +
+      `You are running ${updateInfo.currentVersion}.\n\n` +
       `Release notes:\n${updateInfo.body || 'No release notes'}\n\n` +
-      `Published on: ${updateInfo.date || 'Unknown date'}\n\n` +
+      `Published on: ${updateInfo.date ? new Date(updateInfo.date).toLocaleString() : 'Unknown date'}\n` +
+      (updateInfo.size ? `Download size: ${(updateInfo.size / 1024 / 1024).toFixed(1)} MB\n` : '') +
+      '\n' +
       'Would you like to update now?',
       {
         title: 'Update Available',
-        okLabel: 'Yes, update now',
+        okLabel: 'Yes, download it',
         cancelLabel: 'No, remind me later'
       }
     );
@@ -65,43 +197,29 @@ export async function checkForUpdates() {
         // Show progress dialog by setting the store value
         updateProgressVisible.set(true);
         
-        // Start download and installation in Rust
-        await invoke('download_and_install_update');
-        
-        // Wait until update is complete or has error
-        await new Promise<void>((resolve, reject) => {
-          const unsubComplete = updateComplete.subscribe(value => {
-            if (value) {
-              unsubComplete();
-              unsubError();
-              resolve();
-            }
-          });
-          
-          const unsubError = updateError.subscribe(err => {
-            if (err) {
-              unsubComplete();
-              unsubError();
-              reject(new Error(err));
-            }
-          });
-        });
+        // Download and verify in Rust; installing is a separate step
+        updateProgress.set(null);
+        const pending = await invoke<PendingUpdate>('download_update', { onEvent: downloadChannel() });
         
         // Hide progress dialog
         updateProgressVisible.set(false);
         
-        // When complete, show success message and relaunch
-        await message('Update has been downloaded and will be installed now. The application will restart.', {
-          title: 'Update Ready'
-        });
-        
-        await relaunch();
+        await promptInstall(pending);
       } catch (error) {
         // Hide progress dialog
         updateProgressVisible.set(false);
+        if (isAppError(error) && error.kind === 'Cancelled') {
+          console.log('Update download cancelled');
+          return;
+        }
         
         console.error('Update installation failed:', error);
-        await message(`Failed to install update: ${error}`, {
+        const unverified = isAppError(error) &&
+          (error.kind === 'UpdateSignatureInvalid' || error.kind === 'UpdateChecksumMismatch');
+        const text = unverified
+          ? 'The downloaded update could not be verified and was not installed.'
+          : `Failed to install update: ${errorMessage(error)}`;
+        await message(text, {
           title: 'Update Error'
         });
       }
@@ -110,18 +228,11 @@ export async function checkForUpdates() {
     }
   } catch (error) {
     console.error('Update check failed:', error);
-    await message(`Failed to check for updates: ${error}`, {
+    const text = isAppError(error) && error.kind === 'NetworkUnavailable'
+      ? 'Could not reach the update server. Check your internet connection and try again.'
+      : `Failed to check for updates: ${errorMessage(error)}`;
+    await message(text, {
       title: 'Update Error'
     });
   }
 }
-
-// Called by the progress component when the update is complete
-export function handleUpdateComplete() {
-  updateComplete.set(true);
-}
-
-// Called by the progress component when there's an error
-export function handleUpdateError(error: string) {
-  updateError.set(error);
-}
\ No newline at end of file
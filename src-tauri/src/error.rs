@@ -0,0 +1,126 @@
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+
+/// Error returned by every Tauri command.
+///
+/// Serializes to `{ kind, message, details }`: `kind` is the variant name for
+/// the frontend to branch on, `message` is meant for the user and `details`
+/// carries the underlying cause, if any.
+#[derive(Debug, thiserror::Error)]
+pub enum AppError {
+    #[error("R could not be found")]
+    RNotFound { details: String },
+    #[error("The Shiny apps folder could not be found")]
+    AppsNotFound { details: String },
+    #[error("Unknown Shiny app '{0}'")]
+    UnknownApp(String),
+    #[error("Shiny app '{0}' is already running")]
+    AlreadyRunning(String),
+    #[error("Shiny app '{0}' is not running")]
+    NotRunning(String),
+    #[error("No start operation '{0}' is pending")]
+    UnknownOperation(String),
+    #[error("The operation was cancelled")]
+    Cancelled,
+    #[error("The network settings do not allow this app to start")]
+    NetworkRefused { details: String },
+    #[error("No free port is available for the Shiny app")]
+    PortExhausted { details: String },
+    #[error("The Shiny app did not start within {seconds} s")]
+    StartupTimeout { seconds: u64 },
+    #[error("The Shiny app failed to start")]
+    StartupFailed { details: String },
+    #[error("No update is available")]
+    NoUpdateAvailable,
+    #[error("An update is already being downloaded")]
+    UpdateInProgress,
+    #[error("No update is being downloaded")]
+    NoUpdateDownload,
+    #[error("No previous version is available to roll back to")]
+    NoRollbackAvailable,
+    #[error("The update signature is invalid")]
+    UpdateSignatureInvalid { details: String },
+    #[error("The update does not match its published checksum")]
+    UpdateChecksumMismatch { details: String },
+    #[error("The update server could not be reached")]
+    NetworkUnavailable { details: String },
+    #[error("The update failed")]
+    UpdateFailed { details: String },
+    #[error("{0}")]
+    Internal(String),
+}
+
+impl AppError {
+    /// Variant name, sent as `kind`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            AppError::RNotFound { .. } => "RNotFound",
+            AppError::AppsNotFound { .. } => "AppsNotFound",
+            AppError::UnknownApp(_) => "UnknownApp",
+            AppError::AlreadyRunning(_) => "AlreadyRunning",
+            AppError::NotRunning(_) => "NotRunning",
+            AppError::UnknownOperation(_) => "UnknownOperation",
+            AppError::Cancelled => "Cancelled",
+            AppError::NetworkRefused { .. } => "NetworkRefused",
+            AppError::PortExhausted { .. } => "PortExhausted",
+            AppError::StartupTimeout { .. } => "StartupTimeout",
+            AppError::StartupFailed { .. } => "StartupFailed",
+            AppError::NoUpdateAvailable => "NoUpdateAvailable",
+            AppError::UpdateInProgress => "UpdateInProgress",
+            AppError::NoUpdateDownload => "NoUpdateDownload",
+            AppError::NoRollbackAvailable => "NoRollbackAvailable",
+            AppError::UpdateSignatureInvalid { .. } => "UpdateSignatureInvalid",
+            AppError::UpdateChecksumMismatch { .. } => "UpdateChecksumMismatch",
+            AppError::NetworkUnavailable { .. } => "NetworkUnavailable",
+            AppError::UpdateFailed { .. } => "UpdateFailed",
+            AppError::Internal(_) => "Internal",
+        }
+    }
+
+    pub fn details(&self) -> Option<&str> {
+        match self {
+            AppError::RNotFound { details }
+            | AppError::AppsNotFound { details }
+            | AppError::NetworkRefused { details }
+            | AppError::PortExhausted { details }
+            | AppError::StartupFailed { details }
+            | AppError::UpdateSignatureInvalid { details }
+            | AppError::UpdateChecksumMismatch { details }
+            | AppError::NetworkUnavailable { details }
+            | AppError::UpdateFailed { details } => Some(details),
+            _ => None,
+        }
+    }
+}
+
+impl Serialize for AppError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut s = serializer.serialize_struct("AppError", 3)?;
+        s.serialize_field("kind", self.kind())?;
+        s.serialize_field("message", &self.to_string())?;
+        s.serialize_field("details", &self.details())?;
+        s.end()
+    }
+}
+
+impl From<tauri_plugin_updater::Error> for AppError {
+    fn from(e: tauri_plugin_updater::Error) -> Self {
+        use tauri_plugin_updater::Error;
+        let details = e.to_string();
+        match e {
+            Error::Minisign(_) | Error::Base64(_) | Error::SignatureUtf8(_) => {
+                AppError::UpdateSignatureInvalid { details }
+            }
+            Error::Reqwest(ref e) if e.is_connect() || e.is_timeout() => {
+                AppError::NetworkUnavailable { details }
+            }
+            _ => AppError::UpdateFailed { details },
+        }
+    }
+}
+
+impl From<tauri::Error> for AppError {
+    fn from(e: tauri::Error) -> Self {
+        AppError::Internal(e.to_string())
+    }
+}
@@ -1,9 +1,39 @@
+mod error;
 mod r_shiny;
+mod settings;
+mod shiny_apps;
+mod shiny_config;
+mod shiny_gate;
+mod shiny_log;
+mod shiny_manager;
+mod shiny_network;
+mod shiny_output;
+mod shiny_pidfile;
+mod shiny_ports;
+mod shiny_probe;
+mod shiny_process;
+mod shiny_proxy;
+mod shiny_scheme;
+mod shiny_start;
+mod shiny_watcher;
+mod shiny_window;
+#[cfg(test)]
+mod test_util;
 mod update; // Import the update module // Import the R process module
+mod update_channel;
+mod update_download;
+mod update_installer;
+mod update_pending;
+mod update_rollback;
+mod update_rollout;
 
+use shiny_manager::ShinyManager;
+use shiny_pidfile::{PidFile, PIDFILE_NAME};
 use std::env;
+use std::sync::atomic::AtomicBool;
 use std::sync::{Arc, Mutex};
-use tauri::Manager;
+use tauri::{Manager, RunEvent};
+use tauri_plugin_log::{RotationStrategy, Target, TargetKind};
 use update::UpdateProgress;
 
 fn set_global_env_vars() {
@@ -14,9 +44,9 @@ fn set_global_env_vars() {
         .unwrap_or_else(|_| std::path::PathBuf::new())
         .parent()
         .map(|p| p.to_path_buf())
-        .unwrap_or_else(|| std::path::PathBuf::new());
+        .unwrap_or_default();
 
-    println!("App directory: {:?}", app_dir);
+    log::info!("App directory: {:?}", app_dir);
 
     //if in dev mode then use local path else use appdata path
     let base_path = if is_dev {
@@ -26,7 +56,7 @@ fn set_global_env_vars() {
     } else {
         std::path::PathBuf::from(env::var("APPDATA").unwrap_or_else(|_| ".".into())).join("yourapp")
     };
-    println!("Base path: {:?}", base_path);
+    log::info!("Base path: {:?}", base_path);
     std::fs::create_dir_all(&base_path).unwrap_or_default();
 
     // With these lines:
@@ -59,23 +89,23 @@ fn set_global_env_vars() {
     env::set_var("START_SHINY_PATH", &start_shiny);
     env::set_var("SHINY_URL", &shiny_url);
 
-    println!("Environment Variables Set:");
-    println!("  R_HOME_DIR = {}", r_home);
-    println!("  R_LIBS = {}", r_lib);
-    println!("  RSCRIPT_PATH = {}", rscript);
-    println!("  SHINY_APP_PATH = {}", shiny_app);
-    println!("  START_SHINY_PATH = {}", start_shiny);
-    println!("  SHINY_URL = {}", shiny_url);
-    println!("Checking if files exist:");
-    println!(
+    log::info!("Environment Variables Set:");
+    log::info!("  R_HOME_DIR = {}", r_home);
+    log::info!("  R_LIBS = {}", r_lib);
+    log::info!("  RSCRIPT_PATH = {}", rscript);
+    log::info!("  SHINY_APP_PATH = {}", shiny_app);
+    log::info!("  START_SHINY_PATH = {}", start_shiny);
+    log::info!("  SHINY_URL = {}", shiny_url);
+    log::info!("Checking if files exist:");
+    log::info!(
         "  Rscript exists: {}",
         std::path::Path::new(&rscript_path).exists()
     );
-    println!(
+    log::info!(
         "  start_shiny.R exists: {}",
         std::path::Path::new(&start_shiny_path).exists()
     );
-    println!(
+    log::info!(
         "  shiny_app_path exists: {}",
         std::path::Path::new(&shiny_app_path).exists()
     );
@@ -90,22 +120,70 @@ pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_fs::init())
         .plugin(tauri_plugin_shell::init())
+        .register_asynchronous_uri_scheme_protocol(
+            shiny_scheme::SCHEME,
+            |ctx, request, responder| shiny_scheme::handle(ctx.app_handle(), request, responder),
+        )
         .setup(|app| {
+            // Release builds have no console, so always keep a log file too.
+            // Registered first so nothing logged during setup is lost
+            app.handle().plugin(
+                tauri_plugin_log::Builder::default()
+                    .level(log::LevelFilter::Info)
+                    .targets([
+                        Target::new(TargetKind::Stdout),
+                        Target::new(TargetKind::LogDir {
+                            file_name: Some("app".into()),
+                        }),
+                    ])
+                    .max_file_size(10 * 1024 * 1024)
+                    .rotation_strategy(RotationStrategy::KeepOne)
+                    .build(),
+            )?;
+
             app.manage(update::UpdateState {
                 progress: Arc::new(Mutex::new(UpdateProgress::default())),
+                downloaded: Mutex::new(None),
+                cancel: Mutex::new(None),
+                keeping_running: AtomicBool::new(false),
             });
 
-            if cfg!(debug_assertions) {
-                app.handle().plugin(
-                    tauri_plugin_log::Builder::default()
-                        .level(log::LevelFilter::Info)
-                        .build(),
-                )?;
+            // Reap R processes orphaned by a previous crashed session
+            let pidfile = match app.path().app_data_dir() {
+                Ok(dir) => {
+                    std::fs::create_dir_all(&dir).unwrap_or_default();
+                    let pidfile = PidFile::new(dir.join(PIDFILE_NAME));
+                    pidfile.sweep_orphans();
+                    Some(pidfile)
+                }
+                Err(e) => {
+                    log::warn!("No app data directory, orphan tracking disabled: {}", e);
+                    None
+                }
+            };
+            app.manage(ShinyManager::new(pidfile));
+            app.manage(shiny_ports::PortAllocator::default());
+            app.manage(shiny_log::ShinyLogs::default());
+            // Same port as last run if possible, so app URLs stay the same
+            let mut app_settings = settings::load(app.handle());
+            let proxy = tauri::async_runtime::block_on(shiny_proxy::ShinyProxy::start(
+                app_settings.shiny_proxy_port,
+            ))?;
+            if app_settings.shiny_proxy_port != Some(proxy.port()) {
+                app_settings.shiny_proxy_port = Some(proxy.port());
+                settings::save(app.handle(), &app_settings)
+                    .unwrap_or_else(|e| log::warn!("Failed to save Shiny proxy port: {}", e));
             }
+            app.manage(proxy);
+            app.manage(shiny_start::StartOperations::default());
 
             #[cfg(not(any(target_os = "android", target_os = "ios")))]
             app.handle()
                 .plugin(tauri_plugin_updater::Builder::new().build())?;
+            #[cfg(not(any(target_os = "android", target_os = "ios")))]
+            update::restore_pending(app.handle());
+            #[cfg(not(any(target_os = "android", target_os = "ios")))]
+            update::check_launch(app.handle());
 
             #[cfg(not(any(target_os = "android", target_os = "ios")))]
             app.handle().plugin(tauri_plugin_dialog::init())?;
@@ -118,13 +196,35 @@ pub fn run() {
         .invoke_handler(tauri::generate_handler![
             greet,
             update::check_for_updates, // Move updates to the new module
-            update::download_and_install_update,
+            update::download_update,
+            update::cancel_update_download,
+            update::install_downloaded_update,
+            update::install_on_exit,
+            update::get_pending_update,
             update::get_update_progress,
+            update::get_update_channel,
+            update::set_update_channel,
+            update::get_install_id,
+            update::rollback_update,
+            update::get_rollback_version,
+            update::mark_update_healthy,
             r_shiny::start_r_shiny, // Register R Shiny commands
+            r_shiny::cancel_shiny_start,
             r_shiny::stop_r_shiny,
+            r_shiny::get_shiny_status,
+            r_shiny::open_shiny_window,
+            r_shiny::get_shiny_logs,
+            r_shiny::list_shiny_apps,
             r_shiny::get_rscript_path,
             r_shiny::test_r_script, // Add this line
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running Tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while running Tauri application")
+        .run(|app_handle, event| {
+            if let RunEvent::Exit = event {
+                // Don't leave Rscript holding a port after we are gone
+                r_shiny::stop_all_shiny(app_handle);
+                update::install_pending_on_exit(app_handle);
+            }
+        });
 }
@@ -1,260 +1,580 @@
-use lazy_static::lazy_static;
-use reqwest::blocking::Client;
-use std::net::{TcpListener, TcpStream};
-use std::process::{Child, Command, Stdio};
-use std::time::Duration;
-use std::{env, sync::Mutex, thread, time};
-use tauri::Emitter;
-use tauri_plugin_shell::process::CommandEvent;
-use tauri_plugin_shell::ShellExt;
-
-lazy_static! {
-    static ref R_PROCESS: Mutex<Option<Child>> = Mutex::new(None);
+use crate::error::AppError;
+use crate::shiny_apps::{apps_base_path, discover_apps, find_app, ShinyApp};
+use crate::shiny_config::ShinyConfig;
+use crate::shiny_gate;
+use crate::shiny_log::{LogLine, SessionLog, ShinyLogs, Stream};
+use crate::shiny_manager::{ShinyManager, ShinyState, ShinyStatus};
+use crate::shiny_output::{spawn_reader, OutputParser, OutputSignal};
+use crate::shiny_ports::PortAllocator;
+use crate::shiny_probe::probe;
+use crate::shiny_process::{graceful_stop, new_process_group, StopReport, StopSignal};
+use crate::shiny_proxy::ShinyProxy;
+use crate::shiny_start::{StartOp, StartOperations};
+use crate::shiny_watcher::spawn_watcher;
+use crate::shiny_window;
+use reqwest::Client;
+use serde::Serialize;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+use std::{env, thread};
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::sync::mpsc;
+
+/// Payload of the `shiny-started` event.
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ShinyStarted<'a> {
+    op_id: &'a str,
+    app_id: &'a str,
+    url: &'a str,
+}
+
+/// Payload of the `shiny-error` event.
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ShinyError<'a> {
+    op_id: &'a str,
+    app_id: &'a str,
+    #[serde(flatten)]
+    error: &'a AppError,
 }
 
-const PORT_RANGE: (u16, u16) = (3000, 8000); // Define a sensible port range
+/// Starts the named R Shiny app using the installed `r-win`.
+///
+/// Returns a start-operation id straight away. Progress arrives as
+/// `shiny-start-progress` events and the outcome as `shiny-started` or
+/// `shiny-error`, all tagged with the id. Pass the id to
+/// `cancel_shiny_start` to abort.
+#[tauri::command]
+pub async fn start_r_shiny(
+    app_handle: tauri::AppHandle,
+    app_id: String,
+) -> Result<String, AppError> {
+    let manager = app_handle.state::<ShinyManager>();
+
+    let current = manager.begin_start(&app_handle, &app_id)?;
+    manager.reset_restarts(&app_id);
+
+    let op = app_handle.state::<StartOperations>().begin(&app_id);
+    let op_id = op.id.clone();
+    tauri::async_runtime::spawn(async move {
+        let op_id = op.id.clone();
+        let app_id = op.app_id.clone();
+        let result = match current.url {
+            Some(url) if current.state == ShinyState::Ready => Ok(url),
+            _ => launch_shiny(&app_handle, op).await,
+        };
+        app_handle.state::<StartOperations>().finish(&op_id);
+        if result.is_err() {
+            // Next start gets a fresh session token
+            app_handle.state::<ShinyProxy>().close(&app_id);
+        }
 
-fn find_available_port(start: u16, end: u16) -> Option<u16> {
-    let shiny_url = env::var("SHINY_URL").unwrap_or_else(|_| "http://127.0.0.1".to_string());
+        let emitted = match &result {
+            Ok(url) => app_handle.emit(
+                "shiny-started",
+                ShinyStarted {
+                    op_id: &op_id,
+                    app_id: &app_id,
+                    url,
+                },
+            ),
+            Err(error) => app_handle.emit(
+                "shiny-error",
+                ShinyError {
+                    op_id: &op_id,
+                    app_id: &app_id,
+                    error,
+                },
+            ),
+        };
+        emitted.unwrap_or_else(|e| log::error!("Failed to emit start result: {}", e));
+    });
+    Ok(op_id)
+}
 
-    // Extract host part (remove http:// if present)
-    let host = if shiny_url.starts_with("http://") {
-        &shiny_url["http://".len()..]
+/// Cancels a pending `start_r_shiny` operation. Any half-started child is
+/// killed and its port released.
+#[tauri::command]
+pub fn cancel_shiny_start(app_handle: tauri::AppHandle, op_id: String) -> Result<(), AppError> {
+    if app_handle.state::<StartOperations>().cancel(&op_id) {
+        Ok(())
     } else {
-        &shiny_url
-    };
+        Err(AppError::UnknownOperation(op_id))
+    }
+}
 
-    for port in start..end {
-        if TcpListener::bind(format!("{}:{}", host, port)).is_ok() {
-            return Some(port);
+/// Spawns Rscript and waits for the server to answer. Used for the initial
+/// start and by the watcher for automatic restarts.
+pub(crate) async fn launch_shiny(
+    app_handle: &AppHandle,
+    mut op: StartOp,
+) -> Result<String, AppError> {
+    let result = try_launch(app_handle, &mut op).await;
+    if result.is_err() && op.is_cancelled() {
+        let manager = app_handle.state::<ShinyManager>();
+        if let Some(mut child) = manager.take_child(&op.app_id) {
+            let report = graceful_stop(&mut child, StopSignal::Terminate, Duration::ZERO);
+            manager.forget_child(child.id());
+            manager.transition(app_handle, &op.app_id, ShinyState::Exited(report.exit_code));
+        } else {
+            // Not spawned yet, or `stop_shiny` already stopped it
+            manager.abandon_start(app_handle, &op.app_id);
         }
+        return Err(AppError::Cancelled);
     }
-    None
+    if result.is_err() {
+        // Failed before R was spawned, e.g. a missing Rscript
+        app_handle
+            .state::<ShinyManager>()
+            .abandon_start(app_handle, &op.app_id);
+    }
+    result
 }
 
-/// Starts the R Shiny app using the installed `r-win`.
-#[tauri::command]
-pub fn start_r_shiny(app_handle: tauri::AppHandle) -> Result<String, String> {
-    let rscript_path = env::var("RSCRIPT_PATH").expect("RSCRIPT_PATH not set");
-    let r_home = env::var("R_HOME_DIR").expect("R_HOME_DIR not set");
-    let start_shiny_path = env::var("START_SHINY_PATH").expect("START_SHINY_PATH not set");
-    let r_lib_path = env::var("R_LIB_PATH").expect("R_LIB_PATH not set");
-    let shiny_app_path = env::var("SHINY_APP_PATH").expect("SHINY_APP_PATH not set");
-    let shiny_url = env::var("SHINY_URL").expect("SHINY_URL not set");
+/// Reads one of the R paths set by `set_global_env_vars`.
+fn r_env(name: &str) -> Result<String, AppError> {
+    env::var(name).map_err(|_| AppError::RNotFound {
+        details: format!("{} is not set", name),
+    })
+}
+
+async fn try_launch(app_handle: &AppHandle, op: &mut StartOp) -> Result<String, AppError> {
+    let rscript_path = r_env("RSCRIPT_PATH")?;
+    let r_home = r_env("R_HOME_DIR")?;
+    let start_shiny_path = r_env("START_SHINY_PATH")?;
+    let r_lib_path = r_env("R_LIB_PATH")?;
+    if !Path::new(&rscript_path).exists() {
+        return Err(AppError::RNotFound {
+            details: format!("Rscript not found at {}", rscript_path),
+        });
+    }
+    let app_id = op.app_id.clone();
+    let app_id = app_id.as_str();
+    let app = find_app(&apps_base_path()?, app_id)
+        .ok_or_else(|| AppError::UnknownApp(app_id.to_string()))?;
+    let shiny_app_path = app.path.to_string_lossy().to_string();
+    let config = ShinyConfig::load(&app.path);
+    let manager = app_handle.state::<ShinyManager>().inner().clone();
+    let allocator = app_handle.state::<PortAllocator>().inner().clone();
+    let network = &config.network;
+    network
+        .check()
+        .map_err(|details| AppError::NetworkRefused { details })?;
+    let proxy = app_handle.state::<ShinyProxy>().inner().clone();
+    let gate = shiny_gate::install(&app_handle.path().app_cache_dir()?)
+        .map_err(|details| AppError::StartupFailed { details })?;
 
     let mut retries = 0;
     let max_retries = 4;
     let mut delay = 1000; // Start with 1s delay, increase with retries
+    let mut last_error = None;
 
-    while retries < max_retries {
+    'attempts: while retries < max_retries {
         // Inform frontend we're attempting to start
-        app_handle
-            .emit(
-                "shiny-status",
-                format!("Attempting to start (try {}/{})", retries + 1, max_retries),
-            )
-            .unwrap_or_else(|e| eprintln!("Failed to emit status event: {}", e));
-
-        if let Some(port) = find_available_port(PORT_RANGE.0, PORT_RANGE.1) {
-            println!(
-                "Trying to launch Shiny app on port {} (Attempt {}/{})",
-                port,
-                retries + 1,
-                max_retries
-            );
-
-            let process_result = Command::new(&rscript_path)
-                .arg("--vanilla")
-                .arg(&start_shiny_path)
-                .arg("--verbose")
-                .env("RHOME", &r_home)
-                .env("R_HOME_DIR", &r_home)
-                .env("RE_SHINY_PORT", port.to_string())
-                .env("RE_SHINY_PATH", &shiny_app_path)
-                .env("RE_SHINY_HOST", "0.0.0.0") // Make Shiny bind to all interfaces
-                .env("R_LIBS", &r_lib_path)
-                .env("R_LIBS_USER", &r_lib_path)
-                .env("R_LIBS_SITE", &r_lib_path)
-                .env("R_LIB_PATHS", &r_lib_path)
-                .stdout(Stdio::piped())
-                .stderr(Stdio::piped())
-                .spawn();
-
-            match process_result {
-                Ok(mut process) => {
-                    let pid = process.id();
-                    // Capture output in separate threads
-                    if let Some(stdout) = process.stdout.take() {
-                        std::thread::spawn(move || {
-                            use std::io::{BufRead, BufReader};
-                            let reader = BufReader::new(stdout);
-                            for line in reader.lines() {
-                                if let Ok(line) = line {
-                                    println!("SHINY OUT: {}", line);
-                                }
-                            }
-                        });
-                    }
-                    if let Some(stderr) = process.stderr.take() {
-                        std::thread::spawn(move || {
-                            use std::io::{BufRead, BufReader};
-                            let reader = BufReader::new(stderr);
-                            for line in reader.lines() {
-                                if let Ok(line) = line {
-                                    println!("SHINY ERR: {}", line);
-                                }
-                            }
-                        });
-                    }
-                    *R_PROCESS.lock().unwrap() = Some(process);
-                    println!(
-                        "Shiny process started with PID: {}. Waiting for server to be ready...",
-                        pid
-                    );
+        op.progress(
+            app_handle,
+            format!("Attempting to start (try {}/{})", retries + 1, max_retries),
+        );
+        manager.transition(app_handle, app_id, ShinyState::Starting);
 
-                    // Create the URL
-                    // Check this line - it might need to be:
-                    let full_url = format!("http://127.0.0.1:{}", port);
-
-                    println!("Attempting to connect to URL: {}", full_url);
-                    // Poll to check if Shiny is ready
-                    let client = Client::new();
-                    let mut poll_attempts = 0;
-                    let max_poll_attempts = 6;
-
-                    // Loop to check if Shiny is responding
-                    loop {
-                        if poll_attempts >= max_poll_attempts {
-                            // Too many attempts, kill the process and return error
-                            if let Some(mut p) = R_PROCESS.lock().unwrap().take() {
-                                let _ = p.kill();
-                            }
-                            return Err("Timed out waiting for Shiny server to start".to_string());
+        let mut lease = match allocator.reserve(network.bind_address, &config.ports) {
+            Ok(lease) => lease,
+            Err(e) => {
+                log::error!("{}. Retrying...", e);
+                last_error = Some(AppError::PortExhausted { details: e });
+                retries += 1;
+                op.sleep(Duration::from_millis(delay)).await?;
+                delay *= 2;
+                continue;
+            }
+        };
+        let port = lease.port();
+        log::info!(
+            "Trying to launch Shiny app on port {} (Attempt {}/{})",
+            port,
+            retries + 1,
+            max_retries
+        );
+
+        let mut command = shiny_gate::command(&rscript_path, &gate, &start_shiny_path);
+        command
+            .env("RHOME", &r_home)
+            .env("R_HOME_DIR", &r_home)
+            .env("RE_SHINY_PORT", port.to_string())
+            .env("RE_SHINY_PATH", &shiny_app_path)
+            .env("RE_SHINY_HOST", network.bind_address.to_string())
+            .env("R_LIBS", &r_lib_path)
+            .env("R_LIBS_USER", &r_lib_path)
+            .env("R_LIBS_SITE", &r_lib_path)
+            .env("R_LIB_PATHS", &r_lib_path)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        // Route the stable proxy URL to this port before R comes up, so the
+        // page shows "restarting" rather than an error meanwhile
+        let backend = SocketAddr::new(network.connect_host(), port);
+        // R refuses requests without the token, so only the proxy gets through
+        let token = proxy.open(app_id, backend, network.session_token);
+        if let Some(token) = &token {
+            command.env("RE_SHINY_TOKEN", token);
+        }
+        let full_url = proxy.url(app_id);
+        // Own process group so stop can reach workers R spawns
+        new_process_group(&mut command);
+
+        // Hand the port over to R as late as possible
+        lease.release_socket();
+        match command.spawn() {
+            Ok(mut process) => {
+                let pid = process.id();
+                let stdout = process.stdout.take();
+                let stderr = process.stderr.take();
+                let generation = manager.attach(app_id, process, lease, &full_url);
+                network.announce(app_handle, app_id, port);
+                // Capture output and watch it for readiness and R errors
+                let (signal_tx, mut signals) = mpsc::unbounded_channel();
+                let session_log = SessionLog::create(app_handle, app_id);
+                if let Some(stdout) = stdout {
+                    let signal_tx = signal_tx.clone();
+                    let session_log = session_log.clone();
+                    let mut parser = OutputParser::default();
+                    spawn_reader(stdout, move |line| {
+                        session_log.line(Stream::Stdout, line);
+                        if let Some(signal) = parser.feed(line) {
+                            let _ = signal_tx.send(signal);
+                        }
+                    });
+                }
+                if let Some(stderr) = stderr {
+                    let manager = manager.clone();
+                    let app_id = app_id.to_string();
+                    let mut parser = OutputParser::default();
+                    spawn_reader(stderr, move |line| {
+                        session_log.line(Stream::Stderr, line);
+                        manager.push_stderr(&app_id, generation, line);
+                        if let Some(signal) = parser.feed(line) {
+                            let _ = signal_tx.send(signal);
                         }
+                    });
+                }
+                log::info!(
+                    "Shiny app '{}' started with PID: {}. Waiting for server to be ready...",
+                    app_id,
+                    pid
+                );
 
-                        // Exponential backoff
-                        let wait_ms = 500 * u64::pow(1.2 as u64, poll_attempts as u32);
-                        thread::sleep(Duration::from_millis(wait_ms));
-
-                        // Emit status update
-                        app_handle
-                            .emit(
-                                "shiny-status",
-                                format!(
-                                    "Waiting for server (attempt {}/{})",
-                                    poll_attempts + 1,
-                                    max_poll_attempts
-                                ),
-                            )
-                            .unwrap_or_else(|e| eprintln!("Failed to emit status: {}", e));
-
-                        // Before your client.head request
-                        match TcpStream::connect(format!("127.0.0.1:{}", port)) {
-                            Ok(_) => println!(
-                                "TCP connection to port {} successful, something is listening",
-                                port
-                            ),
-                            Err(e) => println!("TCP connection to port {} failed: {}", port, e),
+                let readiness = &config.readiness;
+                let client = Client::new();
+                let started = Instant::now();
+                let mut attempt = 0;
+
+                // Probe with backoff until the server answers or the policy times out.
+                // Shiny's "Listening on" line or an R error cut the wait short.
+                loop {
+                    // Wait for the next probe unless R already died, in which
+                    // case find out why
+                    let signal = if manager.status(app_handle, app_id).state.is_active() {
+                        let wait = readiness.interval(attempt);
+                        if started.elapsed() + wait > readiness.timeout() {
+                            // Out of time, kill the process and return error
+                            manager.transition(app_handle, app_id, ShinyState::Unhealthy);
+                            let code = manager.take_child(app_id).and_then(|mut p| {
+                                let report =
+                                    graceful_stop(&mut p, config.stop_signal, config.stop_grace());
+                                manager.forget_child(p.id());
+                                report.exit_code
+                            });
+                            manager.transition(app_handle, app_id, ShinyState::Exited(code));
+                            return Err(AppError::StartupTimeout {
+                                seconds: readiness.timeout().as_secs(),
+                            });
                         }
-                        // Try to connect
-                        match client
-                            .head(&full_url)
-                            .timeout(Duration::from_secs(1))
-                            .send()
-                        {
-                            Ok(response) if response.status().is_success() => {
-                                // Server is ready!
-                                app_handle
-                                    .emit("shiny-started", &full_url)
-                                    .unwrap_or_else(|e| {
-                                        eprintln!("Failed to emit started event: {}", e)
-                                    });
-                                return Ok(full_url);
+                        tokio::select! {
+                            slept = op.sleep(wait) => {
+                                slept?;
+                                None
                             }
-                            _ => {
-                                poll_attempts += 1;
-                                continue;
+                            Some(signal) = signals.recv() => Some(signal),
+                        }
+                    } else if op.is_cancelled() {
+                        // Stopped by `stop_shiny`, not crashed
+                        return Err(AppError::Cancelled);
+                    } else {
+                        Some(drain_failure(&mut signals).await.unwrap_or_else(|| {
+                            OutputSignal::Failed("Shiny process exited during startup".to_string())
+                        }))
+                    };
+                    match signal {
+                        Some(OutputSignal::Listening(address)) => {
+                            log::info!("Shiny reports listening on {}", address);
+                            return Ok(mark_ready(
+                                app_handle, &manager, app_id, generation, config, full_url,
+                            ));
+                        }
+                        Some(OutputSignal::PortInUse) => {
+                            // Someone grabbed the port between release and bind
+                            log::warn!(
+                                "Port {} was taken before Shiny could bind it. Retrying on another port...",
+                                port
+                            );
+                            allocator.mark_busy(port);
+                            last_error = Some(AppError::PortExhausted {
+                                details: format!("Port {} was taken by another process", port),
+                            });
+                            if let Some(mut p) = manager.take_child(app_id) {
+                                graceful_stop(&mut p, StopSignal::Terminate, Duration::ZERO);
+                                manager.forget_child(p.id());
                             }
+                            retries += 1;
+                            continue 'attempts;
+                        }
+                        Some(OutputSignal::Failed(message)) => {
+                            log::error!("Shiny app '{}' failed to start: {}", app_id, message);
+                            manager.transition(app_handle, app_id, ShinyState::Crashed);
+                            if let Some(mut p) = manager.take_child(app_id) {
+                                graceful_stop(&mut p, StopSignal::Terminate, Duration::ZERO);
+                                manager.forget_child(p.id());
+                            }
+                            return Err(AppError::StartupFailed { details: message });
+                        }
+                        None => {}
+                    }
+
+                    // Emit status update
+                    op.progress(
+                        app_handle,
+                        format!(
+                            "Waiting for server (attempt {}, {} s of {} s)",
+                            attempt + 1,
+                            started.elapsed().as_secs(),
+                            readiness.timeout().as_secs()
+                        ),
+                    );
+
+                    match probe(
+                        &client,
+                        &readiness.probe,
+                        network.connect_host(),
+                        port,
+                        token.as_deref(),
+                    )
+                    .await
+                    {
+                        Ok(()) => {
+                            return Ok(mark_ready(
+                                app_handle, &manager, app_id, generation, config, full_url,
+                            ));
+                        }
+                        Err(e) => {
+                            log::info!("Readiness probe {} failed: {}", attempt + 1, e);
+                            attempt += 1;
                         }
                     }
-                }
-                Err(e) => {
-                    eprintln!("Failed to start Shiny app: {}. Retrying...", e);
-                    retries += 1;
-                    thread::sleep(time::Duration::from_millis(delay));
-                    delay *= 2; // Exponential backoff
                 }
             }
-        } else {
-            eprintln!(
-                "No available ports in range {}-{}. Retrying...",
-                PORT_RANGE.0, PORT_RANGE.1
-            );
-            retries += 1;
-            thread::sleep(time::Duration::from_millis(delay));
-            delay *= 2;
+            Err(e) => {
+                log::error!("Failed to start Shiny app: {}. Retrying...", e);
+                last_error = Some(if e.kind() == std::io::ErrorKind::NotFound {
+                    AppError::RNotFound {
+                        details: format!("{}: {}", rscript_path, e),
+                    }
+                } else {
+                    AppError::StartupFailed {
+                        details: e.to_string(),
+                    }
+                });
+                retries += 1;
+                op.sleep(Duration::from_millis(delay)).await?;
+                delay *= 2; // Exponential backoff
+            }
         }
     }
 
-    // Emit failure event
+    manager.transition(app_handle, app_id, ShinyState::Idle);
+    Err(last_error.unwrap_or_else(|| AppError::StartupFailed {
+        details: "No start attempts were made".to_string(),
+    }))
+}
+
+/// After R dies, gives the reader threads a moment to deliver the reason:
+/// a port conflict or an R error.
+async fn drain_failure(
+    signals: &mut mpsc::UnboundedReceiver<OutputSignal>,
+) -> Option<OutputSignal> {
+    while let Ok(Some(signal)) =
+        tokio::time::timeout(Duration::from_millis(500), signals.recv()).await
+    {
+        if matches!(signal, OutputSignal::PortInUse | OutputSignal::Failed(_)) {
+            return Some(signal);
+        }
+    }
+    None
+}
+
+/// Server is ready: record it and start watching the child.
+fn mark_ready(
+    app_handle: &AppHandle,
+    manager: &ShinyManager,
+    app_id: &str,
+    generation: u64,
+    config: ShinyConfig,
+    url: String,
+) -> String {
+    manager.transition(app_handle, app_id, ShinyState::Ready);
+    spawn_watcher(app_handle.clone(), app_id.to_string(), generation, config);
+    url
+}
+
+/// Stops the named R Shiny app: signals its process group, waits for the
+/// configured grace period, then kills whatever is left.
+#[tauri::command]
+pub async fn stop_r_shiny(
+    app_handle: tauri::AppHandle,
+    app_id: String,
+) -> Result<StopReport, AppError> {
+    tauri::async_runtime::spawn_blocking(move || stop_shiny(&app_handle, &app_id))
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))?
+}
+
+pub(crate) fn stop_shiny(app_handle: &AppHandle, app_id: &str) -> Result<StopReport, AppError> {
+    // A launch still waiting for readiness ends as cancelled, not failed
+    app_handle.state::<StartOperations>().cancel_app(app_id);
+    let manager = app_handle.state::<ShinyManager>();
+    manager.refresh(app_handle, app_id);
+    let Some(mut child) = manager.take_child(app_id) else {
+        return Err(AppError::NotRunning(app_id.to_string()));
+    };
+
+    // Stopping must not depend on the app folder still being found
+    let config = apps_base_path()
+        .ok()
+        .and_then(|base| find_app(&base, app_id))
+        .map(|app| ShinyConfig::load(&app.path))
+        .unwrap_or_default();
+    manager.transition(app_handle, app_id, ShinyState::Stopping);
+    let report = graceful_stop(&mut child, config.stop_signal, config.stop_grace());
+    manager.forget_child(child.id());
+    manager.transition(app_handle, app_id, ShinyState::Exited(report.exit_code));
+    app_handle.state::<ShinyProxy>().close(app_id);
+    shiny_window::close(app_handle, app_id);
+
+    // Emit stopped event
     app_handle
-        .emit("shiny-error", "Failed to launch Shiny app")
-        .unwrap_or_else(|e| eprintln!("Failed to emit error event: {}", e));
+        .emit("shiny-stopped", app_id)
+        .unwrap_or_else(|e| log::error!("Failed to emit stopped event: {}", e));
+    Ok(report)
+}
+
+/// Stops every running app in parallel. Called when the Tauri app exits.
+pub(crate) fn stop_all_shiny(app_handle: &AppHandle) {
+    let app_ids = app_handle.state::<ShinyManager>().running_app_ids();
+    let handles: Vec<_> = app_ids
+        .into_iter()
+        .map(|app_id| {
+            let app_handle = app_handle.clone();
+            thread::spawn(move || {
+                if let Err(e) = stop_shiny(&app_handle, &app_id) {
+                    log::error!("Failed to stop Shiny app '{}' on exit: {}", app_id, e);
+                }
+            })
+        })
+        .collect();
+    for handle in handles {
+        let _ = handle.join();
+    }
+}
 
-    Err("Failed to launch Shiny app.".to_string())
+/// Returns the current state of the named Shiny app.
+#[tauri::command]
+pub fn get_shiny_status(app_handle: tauri::AppHandle, app_id: String) -> ShinyStatus {
+    app_handle
+        .state::<ShinyManager>()
+        .status(&app_handle, &app_id)
 }
 
-/// Stops the running R process.
+/// Opens the running app in its own window, or focuses the window if it is
+/// already open. The window closes when the app is stopped.
 #[tauri::command]
-pub fn stop_r_shiny(app_handle: tauri::AppHandle) -> Result<(), String> {
-    let mut process_guard = R_PROCESS.lock().unwrap();
-    if let Some(mut child) = process_guard.take() {
-        match child.kill() {
-            Ok(_) => {
-                // Emit stopped event
-                app_handle
-                    .emit("shiny-stopped", "Shiny app stopped")
-                    .unwrap_or_else(|e| eprintln!("Failed to emit stopped event: {}", e));
-                Ok(())
-            }
-            Err(e) => Err(format!("Failed to stop R: {}", e)),
+pub async fn open_shiny_window(
+    app_handle: tauri::AppHandle,
+    app_id: String,
+) -> Result<(), AppError> {
+    let status = app_handle
+        .state::<ShinyManager>()
+        .status(&app_handle, &app_id);
+    match status.url {
+        Some(url) if status.state == ShinyState::Ready => {
+            shiny_window::open(&app_handle, &app_id, &url).map_err(AppError::Internal)
         }
-    } else {
-        Err("No R process running".to_string())
+        _ => Err(AppError::NotRunning(app_id)),
     }
 }
 
+/// Returns buffered R output for the app, oldest first. Pass the `seq` of the
+/// last line already shown as `since` to get only newer lines.
+#[tauri::command]
+pub fn get_shiny_logs(
+    app_handle: tauri::AppHandle,
+    app_id: String,
+    since: Option<u64>,
+) -> Vec<LogLine> {
+    app_handle.state::<ShinyLogs>().since(&app_id, since)
+}
+
+/// A discovered app together with its current status.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ShinyAppInfo {
+    #[serde(flatten)]
+    pub app: ShinyApp,
+    pub status: ShinyStatus,
+}
+
+/// Lists the app directories under the base `shiny` folder.
+#[tauri::command]
+pub fn list_shiny_apps(app_handle: tauri::AppHandle) -> Result<Vec<ShinyAppInfo>, AppError> {
+    let manager = app_handle.state::<ShinyManager>();
+    Ok(discover_apps(&apps_base_path()?)
+        .into_iter()
+        .map(|app| ShinyAppInfo {
+            status: manager.status(&app_handle, &app.id),
+            app,
+        })
+        .collect())
+}
+
 /// Returns the resolved path to `Rscript.exe`
 #[tauri::command]
-pub fn get_rscript_path(app_handle: tauri::AppHandle) -> Result<String, String> {
-    let rscript_path = env::var("RSCRIPT_PATH").expect("RSCRIPT_PATH not set");
-    Ok(rscript_path)
+pub fn get_rscript_path() -> Result<String, AppError> {
+    r_env("RSCRIPT_PATH")
 }
 
 /// Test executing R code directly (avoiding file issues)
 #[tauri::command]
-pub fn test_r_script(app_handle: tauri::AppHandle) -> Result<String, String> {
-    let rscript_path = env::var("RSCRIPT_PATH").expect("RSCRIPT_PATH not set");
-    let r_home = env::var("R_HOME_DIR").expect("R_HOME_DIR not set");
-    let r_lib_path = env::var("R_LIB_PATH").expect("R_LIB_PATH not set");
+pub fn test_r_script() -> Result<String, AppError> {
+    let rscript_path = r_env("RSCRIPT_PATH")?;
+    let r_home = r_env("R_HOME_DIR")?;
+    let r_lib_path = r_env("R_LIB_PATH")?;
 
-    println!("Using Rscript from: {}", rscript_path);
+    log::info!("Using Rscript from: {}", rscript_path);
 
     // Verify the R executable exists
     if !std::path::Path::new(&rscript_path).exists() {
-        return Err(format!("R executable not found at: {}", rscript_path));
+        return Err(AppError::RNotFound {
+            details: format!("R executable not found at: {}", rscript_path),
+        });
     }
 
     // Path to the test script (we'll check if it exists but won't execute it directly)
     let test_script_path = "H:/1-Git/grade-tool-tauri/src-tauri/assets/test.R";
-    println!("Looking for test script at: {}", test_script_path);
-
-    // Create the test script file if it doesn't exist (for reference)
-    let test_script_exists = std::path::Path::new(test_script_path).exists();
+    log::info!("Looking for test script at: {}", test_script_path);
 
     // Read script content but execute using -e instead of -f
-    match std::fs::read_to_string(&test_script_path) {
-        Ok(contents) => {
+    match std::fs::read_to_string(test_script_path) {
+        Ok(_) => {
             // Extract only the actual commands (skip comments)
 
             // Use -e to run the code directly
@@ -268,17 +588,17 @@ pub fn test_r_script(app_handle: tauri::AppHandle) -> Result<String, String> {
                 .env("R_LIBS_SITE", &r_lib_path)
                 .env("R_LIB_PATHS", &r_lib_path)
                 .output()
-                .map_err(|e| format!("Failed to execute R code: {}", e))?;
-            println!("R script: {:?}", output);
+                .map_err(|e| AppError::Internal(format!("Failed to execute R code: {}", e)))?;
+            log::info!("R script: {:?}", output);
             // Convert output to strings
             let stdout = String::from_utf8_lossy(&output.stdout).to_string();
             let stderr = String::from_utf8_lossy(&output.stderr).to_string();
 
             // Log the output
-            println!("R SCRIPT STDOUT: {}", stdout);
+            log::info!("R SCRIPT STDOUT: {}", stdout);
 
             if !stderr.is_empty() {
-                println!("R SCRIPT STDERR: {}", stderr);
+                log::info!("R SCRIPT STDERR: {}", stderr);
             }
 
             // Check exit status
@@ -288,17 +608,17 @@ pub fn test_r_script(app_handle: tauri::AppHandle) -> Result<String, String> {
                     stdout
                 ))
             } else {
-                Err(format!(
+                Err(AppError::Internal(format!(
                     "R script execution failed with code: {:?}\nStderr: {}",
                     output.status.code(),
                     stderr
-                ))
+                )))
             }
         }
         Err(e) => {
             // Fallback to hardcoded commands if file read fails
-            println!("Failed to read script: {}. Using hardcoded commands.", e);
-            Err(format!("Failed to read script: {}", e))
+            log::info!("Failed to read script: {}. Using hardcoded commands.", e);
+            Err(AppError::Internal(format!("Failed to read script: {}", e)))
         }
     }
 }
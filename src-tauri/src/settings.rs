@@ -0,0 +1,46 @@
+use crate::error::AppError;
+use crate::update_channel::UpdateChannel;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use tauri::{AppHandle, Manager};
+
+/// File in app data holding the user's settings.
+const SETTINGS_FILE: &str = "settings.json";
+
+/// User preferences kept across restarts. Missing fields use defaults.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Settings {
+    pub update_channel: UpdateChannel,
+    /// Port the Shiny proxy had last run, tried first on the next start.
+    pub shiny_proxy_port: Option<u16>,
+}
+
+fn path(app_handle: &AppHandle) -> Result<PathBuf, AppError> {
+    Ok(app_handle.path().app_data_dir()?.join(SETTINGS_FILE))
+}
+
+/// The saved settings, or the defaults if there are none yet.
+pub fn load(app_handle: &AppHandle) -> Settings {
+    let Ok(path) = path(app_handle) else {
+        return Settings::default();
+    };
+    match fs::read_to_string(&path) {
+        Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|e| {
+            log::warn!("Ignoring invalid {}: {}", path.display(), e);
+            Settings::default()
+        }),
+        Err(_) => Settings::default(),
+    }
+}
+
+pub fn save(app_handle: &AppHandle, settings: &Settings) -> Result<(), AppError> {
+    let path = path(app_handle)?;
+    let json =
+        serde_json::to_string_pretty(settings).map_err(|e| AppError::Internal(e.to_string()))?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| AppError::Internal(e.to_string()))?;
+    }
+    fs::write(&path, json).map_err(|e| AppError::Internal(format!("{}: {}", path.display(), e)))
+}
@@ -0,0 +1,64 @@
+use crate::error::AppError;
+use serde::Serialize;
+use std::env;
+use std::path::{Path, PathBuf};
+
+/// App id used when the base `shiny` folder is itself a single app.
+pub const DEFAULT_APP_ID: &str = "default";
+
+/// A Shiny app directory found under the base `shiny` folder.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ShinyApp {
+    pub id: String,
+    pub path: PathBuf,
+}
+
+/// True if the directory looks like a Shiny app (`app.R` or `ui.R` + `server.R`).
+fn is_shiny_app(dir: &Path) -> bool {
+    dir.join("app.R").is_file() || (dir.join("ui.R").is_file() && dir.join("server.R").is_file())
+}
+
+/// Base folder holding the app directories (`SHINY_APP_PATH`).
+pub fn apps_base_path() -> Result<PathBuf, AppError> {
+    env::var("SHINY_APP_PATH")
+        .map(PathBuf::from)
+        .map_err(|_| AppError::AppsNotFound {
+            details: "SHINY_APP_PATH is not set".to_string(),
+        })
+}
+
+/// Lists the app directories directly under `base`, sorted by id.
+/// A base folder that is itself an app is reported as `default`.
+pub fn discover_apps(base: &Path) -> Vec<ShinyApp> {
+    let mut apps = Vec::new();
+    if is_shiny_app(base) {
+        apps.push(ShinyApp {
+            id: DEFAULT_APP_ID.to_string(),
+            path: base.to_path_buf(),
+        });
+    }
+
+    if let Ok(entries) = std::fs::read_dir(base) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if !path.is_dir() || !is_shiny_app(&path) {
+                continue;
+            }
+            if let Some(id) = path.file_name().and_then(|n| n.to_str()) {
+                apps.push(ShinyApp {
+                    id: id.to_string(),
+                    path: path.clone(),
+                });
+            }
+        }
+    }
+
+    apps.sort_by(|a, b| a.id.cmp(&b.id));
+    apps
+}
+
+/// Looks up a discovered app by id.
+pub fn find_app(base: &Path, app_id: &str) -> Option<ShinyApp> {
+    discover_apps(base).into_iter().find(|app| app.id == app_id)
+}
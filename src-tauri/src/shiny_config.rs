@@ -0,0 +1,138 @@
+use crate::shiny_network::NetworkPolicy;
+use crate::shiny_ports::PortStrategy;
+use crate::shiny_probe::ReadinessPolicy;
+use crate::shiny_process::StopSignal;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::Duration;
+
+/// Name of the optional per-app config file inside the Shiny app directory.
+pub const CONFIG_FILE: &str = "shiny.json";
+
+/// What the watcher does when the Shiny process exits on its own.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(tag = "mode", rename_all = "kebab-case")]
+pub enum RestartPolicy {
+    /// Leave the process dead.
+    Never,
+    /// Restart after a crash, up to `max_retries` times.
+    OnFailure { max_retries: u32, backoff_ms: u64 },
+    /// Restart after any exit, including `stopApp()`.
+    Always { backoff_ms: u64 },
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        RestartPolicy::OnFailure {
+            max_retries: 3,
+            backoff_ms: 1000,
+        }
+    }
+}
+
+impl RestartPolicy {
+    /// Delay before restart number `attempt` (1-based), doubling up to 30 s.
+    /// Returns `None` when the policy does not allow another restart.
+    pub fn next_delay(&self, crashed: bool, attempt: u32) -> Option<u64> {
+        let backoff_ms = match *self {
+            RestartPolicy::Never => return None,
+            RestartPolicy::OnFailure { .. } if !crashed => return None,
+            RestartPolicy::OnFailure {
+                max_retries,
+                backoff_ms,
+            } => {
+                if attempt > max_retries {
+                    return None;
+                }
+                backoff_ms
+            }
+            RestartPolicy::Always { backoff_ms } => backoff_ms,
+        };
+        let factor = 1u64 << attempt.saturating_sub(1).min(16);
+        Some(backoff_ms.saturating_mul(factor).min(30_000))
+    }
+}
+
+/// Per-app settings read from `shiny.json`. Missing fields use defaults.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ShinyConfig {
+    pub restart: RestartPolicy,
+    /// Which address the app listens on.
+    pub network: NetworkPolicy,
+    /// Which port the app listens on.
+    pub ports: PortStrategy,
+    /// How to decide the server is up after spawning R.
+    pub readiness: ReadinessPolicy,
+    /// Signal sent to the process group when stopping.
+    pub stop_signal: StopSignal,
+    /// How long to wait after `stop_signal` before killing the group.
+    pub stop_grace_ms: u64,
+}
+
+impl Default for ShinyConfig {
+    fn default() -> Self {
+        Self {
+            restart: RestartPolicy::default(),
+            network: NetworkPolicy::default(),
+            ports: PortStrategy::default(),
+            readiness: ReadinessPolicy::default(),
+            stop_signal: StopSignal::default(),
+            stop_grace_ms: 5000,
+        }
+    }
+}
+
+impl ShinyConfig {
+    pub fn stop_grace(&self) -> Duration {
+        Duration::from_millis(self.stop_grace_ms)
+    }
+
+    /// Loads `shiny.json` from the app directory, falling back to defaults.
+    pub fn load(app_dir: &Path) -> Self {
+        let path = app_dir.join(CONFIG_FILE);
+        match std::fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|e| {
+                log::warn!("Invalid {}: {}. Using defaults.", path.display(), e);
+                ShinyConfig::default()
+            }),
+            Err(_) => ShinyConfig::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn restart_delay_doubles_up_to_the_cap() {
+        let policy = RestartPolicy::Always { backoff_ms: 1000 };
+        let delays: Vec<_> = (1..=7)
+            .map(|attempt| policy.next_delay(true, attempt))
+            .collect();
+        assert_eq!(
+            delays,
+            [1000, 2000, 4000, 8000, 16_000, 30_000, 30_000].map(Some)
+        );
+        assert_eq!(policy.next_delay(false, 100), Some(30_000));
+    }
+
+    #[test]
+    fn on_failure_stops_after_max_retries() {
+        let policy = RestartPolicy::OnFailure {
+            max_retries: 3,
+            backoff_ms: 500,
+        };
+        assert_eq!(policy.next_delay(true, 1), Some(500));
+        assert_eq!(policy.next_delay(true, 3), Some(2000));
+        assert_eq!(policy.next_delay(true, 4), None);
+        // A clean exit is not a failure
+        assert_eq!(policy.next_delay(false, 1), None);
+    }
+
+    #[test]
+    fn never_restarts() {
+        assert_eq!(RestartPolicy::Never.next_delay(true, 1), None);
+    }
+}
@@ -0,0 +1,131 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// R script run in front of `start-shiny.R` that makes Shiny itself refuse
+/// requests without the session token, not just the proxy.
+const GATE_SCRIPT: &str = include_str!("shiny_gate.R");
+const GATE_FILE: &str = "shiny-gate.R";
+
+/// Writes the gate script into `dir` and returns its path. Rewritten on every
+/// launch so it always matches this build.
+pub fn install(dir: &Path) -> Result<PathBuf, String> {
+    let path = dir.join(GATE_FILE);
+    fs::create_dir_all(dir)
+        .and_then(|_| fs::write(&path, GATE_SCRIPT))
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(path)
+}
+
+/// Rscript command that runs `start_script` behind the gate. The token
+/// itself is passed in `RE_SHINY_TOKEN`; without it the gate lets
+/// everything through.
+pub fn command(rscript: &str, gate: &Path, start_script: &str) -> Command {
+    let mut command = Command::new(rscript);
+    command
+        .arg("--vanilla")
+        .arg(gate)
+        .arg("--verbose")
+        .env("RE_SHINY_SCRIPT", start_script);
+    command
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shiny_proxy::BACKEND_TOKEN_HEADER;
+    use crate::test_util::TempDir;
+    use std::io::{Read, Write};
+    use std::net::{Ipv4Addr, TcpListener, TcpStream};
+    use std::process::Stdio;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    const START_SCRIPT: &str = r#"shiny::runApp(
+  Sys.getenv("RE_SHINY_PATH"),
+  port = as.integer(Sys.getenv("RE_SHINY_PORT")),
+  host = Sys.getenv("RE_SHINY_HOST"),
+  launch.browser = FALSE
+)
+"#;
+    const APP: &str = r#"library(shiny)
+shinyApp(fluidPage("grading data"), function(input, output) {})
+"#;
+
+    fn has_shiny() -> bool {
+        Command::new("Rscript")
+            .args(["--vanilla", "-e", "library(shiny)"])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|s| s.success())
+    }
+
+    /// Status code of a plain GET straight to R, bypassing the proxy.
+    fn status(port: u16, path: &str, token: Option<&str>) -> Option<u16> {
+        let mut stream = TcpStream::connect((Ipv4Addr::LOCALHOST, port)).ok()?;
+        stream.set_read_timeout(Some(Duration::from_secs(5))).ok()?;
+        let header = token
+            .map(|token| format!("{}: {}\r\n", BACKEND_TOKEN_HEADER, token))
+            .unwrap_or_default();
+        write!(
+            stream,
+            "GET {} HTTP/1.0\r\nHost: 127.0.0.1:{}\r\n{}\r\n",
+            path, port, header
+        )
+        .ok()?;
+        let mut response = String::new();
+        let _ = stream.read_to_string(&mut response);
+        response.split_whitespace().nth(1)?.parse().ok()
+    }
+
+    #[test]
+    fn r_refuses_requests_without_the_token() {
+        // Machines without R (or without shiny) cannot run this
+        if !has_shiny() {
+            eprintln!("skipping: Rscript with shiny not found");
+            return;
+        }
+        let dir = TempDir::new("shiny-gate-test");
+        let app_dir = dir.0.join("app");
+        fs::create_dir_all(&app_dir).unwrap();
+        fs::write(app_dir.join("app.R"), APP).unwrap();
+        let start_script = dir.0.join("start-shiny.R");
+        fs::write(&start_script, START_SCRIPT).unwrap();
+        let gate = install(&dir.0).unwrap();
+
+        let free = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let port = free.local_addr().unwrap().port();
+        drop(free);
+
+        let mut child = command("Rscript", &gate, &start_script.to_string_lossy())
+            .env("RE_SHINY_PORT", port.to_string())
+            .env("RE_SHINY_PATH", &app_dir)
+            .env("RE_SHINY_HOST", "127.0.0.1")
+            .env("RE_SHINY_TOKEN", "secret")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+
+        let started = Instant::now();
+        let mut with_token = None;
+        while started.elapsed() < Duration::from_secs(60) {
+            with_token = status(port, "/", Some("secret"));
+            if with_token.is_some() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(200));
+        }
+        let without_token = status(port, "/", None);
+        let wrong_token = status(port, "/", Some("guess"));
+        let static_without_token = status(port, "/shared/shiny.min.js", None);
+        let _ = child.kill();
+        let _ = child.wait();
+
+        assert_eq!(with_token, Some(200));
+        assert_eq!(without_token, Some(403));
+        assert_eq!(wrong_token, Some(403));
+        assert_eq!(static_without_token, Some(403));
+    }
+}
@@ -0,0 +1,232 @@
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{LineWriter, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Emitter, Manager};
+use time::macros::format_description;
+use time::OffsetDateTime;
+
+/// `log` target for R output.
+pub const LOG_TARGET: &str = "shiny";
+/// Session log files kept per app; older ones are deleted.
+const MAX_SESSION_FILES: usize = 10;
+/// A session log rolls over to a new part once it reaches this size.
+const MAX_FILE_BYTES: u64 = 10 * 1024 * 1024;
+/// Lines kept in memory per app for `get_shiny_logs`.
+const BUFFER_LINES: usize = 2000;
+
+/// Which R output stream a line came from.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+impl fmt::Display for Stream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Stream::Stdout => "stdout",
+            Stream::Stderr => "stderr",
+        })
+    }
+}
+
+/// One line of R output, as sent in the `shiny-log` event.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogLine {
+    /// Increases by one per line across all apps; pass the last one seen
+    /// as `since` to `get_shiny_logs` to resume.
+    pub seq: u64,
+    pub app_id: String,
+    pub stream: Stream,
+    /// Milliseconds since the Unix epoch.
+    pub timestamp: u64,
+    pub line: String,
+}
+
+/// Recent R output per app in bounded ring buffers. Stored in Tauri state.
+#[derive(Default)]
+pub struct ShinyLogs {
+    inner: Mutex<LogBuffers>,
+}
+
+#[derive(Default)]
+struct LogBuffers {
+    next_seq: u64,
+    apps: HashMap<String, VecDeque<LogLine>>,
+}
+
+impl ShinyLogs {
+    fn push(&self, app_id: &str, stream: Stream, text: &str) -> LogLine {
+        let mut inner = self.inner.lock().unwrap();
+        inner.next_seq += 1;
+        let line = LogLine {
+            seq: inner.next_seq,
+            app_id: app_id.to_string(),
+            stream,
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or(0),
+            line: text.to_string(),
+        };
+        let buffer = inner.apps.entry(app_id.to_string()).or_default();
+        if buffer.len() == BUFFER_LINES {
+            buffer.pop_front();
+        }
+        buffer.push_back(line.clone());
+        line
+    }
+
+    /// Buffered lines for `app_id` newer than `since`, oldest first.
+    pub fn since(&self, app_id: &str, since: Option<u64>) -> Vec<LogLine> {
+        let inner = self.inner.lock().unwrap();
+        let Some(buffer) = inner.apps.get(app_id) else {
+            return Vec::new();
+        };
+        let since = since.unwrap_or(0);
+        buffer
+            .iter()
+            .filter(|line| line.seq > since)
+            .cloned()
+            .collect()
+    }
+}
+
+fn now() -> OffsetDateTime {
+    OffsetDateTime::now_local().unwrap_or_else(|_| OffsetDateTime::now_utc())
+}
+
+struct LogFile {
+    writer: LineWriter<File>,
+    written: u64,
+    part: u32,
+}
+
+/// Output of one R process: every line goes to the `shiny` log target,
+/// `<app log dir>/shiny/<app id>/<session>.log`, the in-memory buffer and a
+/// `shiny-log` event.
+pub struct SessionLog {
+    app_handle: AppHandle,
+    app_id: String,
+    /// Session file path without the `.log` extension.
+    base: Option<PathBuf>,
+    file: Mutex<Option<LogFile>>,
+}
+
+impl SessionLog {
+    /// Starts a new session log for `app_id`, pruning old sessions. If the
+    /// file cannot be created, lines still reach the `log` target.
+    pub fn create(app_handle: &AppHandle, app_id: &str) -> Arc<Self> {
+        let opened = app_handle
+            .path()
+            .app_log_dir()
+            .map_err(|e| e.to_string())
+            .and_then(|dir| {
+                let dir = dir.join("shiny").join(app_id);
+                fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+                prune(&dir);
+                let stamp = now()
+                    .format(format_description!(
+                        "[year][month][day]-[hour][minute][second]-[subsecond digits:3]"
+                    ))
+                    .map_err(|e| e.to_string())?;
+                let base = dir.join(stamp);
+                let file = open_part(&base, 0)?;
+                Ok((base, file))
+            });
+        let (base, file) = match opened {
+            Ok((base, file)) => (Some(base), Some(file)),
+            Err(e) => {
+                log::error!("Failed to create session log for '{}': {}", app_id, e);
+                (None, None)
+            }
+        };
+        Arc::new(Self {
+            app_handle: app_handle.clone(),
+            app_id: app_id.to_string(),
+            base,
+            file: Mutex::new(file),
+        })
+    }
+
+    /// Records one line of R output.
+    pub fn line(&self, stream: Stream, text: &str) {
+        log::info!(target: LOG_TARGET, "[{}] [{}] {}", self.app_id, stream, text);
+
+        let line = self
+            .app_handle
+            .state::<ShinyLogs>()
+            .push(&self.app_id, stream, text);
+        self.app_handle
+            .emit("shiny-log", line)
+            .unwrap_or_else(|e| log::error!("Failed to emit log event: {}", e));
+
+        let timestamp = now()
+            .format(format_description!(
+                "[year]-[month]-[day] [hour]:[minute]:[second].[subsecond digits:3]"
+            ))
+            .unwrap_or_default();
+        let entry = format!("{} [{}] {}\n", timestamp, stream, text);
+
+        let mut file = self.file.lock().unwrap();
+        let Some(current) = file.as_mut() else {
+            return;
+        };
+        if current.written >= MAX_FILE_BYTES {
+            if let Some(base) = &self.base {
+                match open_part(base, current.part + 1) {
+                    Ok(next) => *current = next,
+                    Err(e) => log::error!("Failed to roll session log: {}", e),
+                }
+            }
+        }
+        match current.writer.write_all(entry.as_bytes()) {
+            Ok(()) => current.written += entry.len() as u64,
+            Err(e) => {
+                log::error!("Failed to write session log for '{}': {}", self.app_id, e);
+                *file = None;
+            }
+        }
+    }
+}
+
+fn open_part(base: &std::path::Path, part: u32) -> Result<LogFile, String> {
+    let name = base.file_name().unwrap_or_default().to_string_lossy();
+    let path = if part == 0 {
+        base.with_file_name(format!("{}.log", name))
+    } else {
+        base.with_file_name(format!("{}.{}.log", name, part))
+    };
+    let file = File::create(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(LogFile {
+        writer: LineWriter::new(file),
+        written: 0,
+        part,
+    })
+}
+
+/// Deletes the oldest session files so that, with the one about to be
+/// created, at most `MAX_SESSION_FILES` remain. Names sort by start time.
+fn prune(dir: &std::path::Path) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "log"))
+        .collect();
+    files.sort();
+    let excess = (files.len() + 1).saturating_sub(MAX_SESSION_FILES);
+    for path in files.into_iter().take(excess) {
+        if let Err(e) = fs::remove_file(&path) {
+            log::warn!("Failed to remove old session log {}: {}", path.display(), e);
+        }
+    }
+}
@@ -0,0 +1,438 @@
+use crate::error::AppError;
+use crate::shiny_pidfile::{PidEntry, PidFile};
+use crate::shiny_ports::PortLease;
+use crate::shiny_process::{graceful_stop, StopSignal};
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::process::Child;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Emitter};
+
+/// Lifecycle of the managed Shiny process.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "state", content = "code", rename_all = "camelCase")]
+pub enum ShinyState {
+    Idle,
+    Starting,
+    Ready,
+    Unhealthy,
+    Stopping,
+    Exited(Option<i32>),
+    Crashed,
+}
+
+impl ShinyState {
+    /// True while a child process is expected to be alive.
+    pub fn is_active(&self) -> bool {
+        matches!(
+            self,
+            ShinyState::Starting | ShinyState::Ready | ShinyState::Unhealthy | ShinyState::Stopping
+        )
+    }
+}
+
+/// Snapshot of one app's session returned by `get_shiny_status`.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ShinyStatus {
+    pub app_id: String,
+    #[serde(flatten)]
+    pub state: ShinyState,
+    pub port: Option<u16>,
+    pub url: Option<String>,
+    pub pid: Option<u32>,
+    /// Milliseconds since the Unix epoch.
+    pub started_at: Option<u64>,
+    /// Exit code of the last process, if it exited.
+    pub exit_code: Option<i32>,
+    /// Last stderr lines captured before the last exit.
+    pub last_stderr: Vec<String>,
+    /// Automatic restarts since the app was last started by the user.
+    pub restarts: u32,
+}
+
+impl ShinyStatus {
+    fn new(app_id: &str) -> Self {
+        Self {
+            app_id: app_id.to_string(),
+            state: ShinyState::Idle,
+            port: None,
+            url: None,
+            pid: None,
+            started_at: None,
+            exit_code: None,
+            last_stderr: Vec::new(),
+            restarts: 0,
+        }
+    }
+}
+
+/// Payload of the `shiny-state-changed` event.
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ShinyTransition {
+    from: ShinyState,
+    status: ShinyStatus,
+}
+
+/// Number of stderr lines kept for crash reports.
+const STDERR_TAIL_LINES: usize = 20;
+
+struct ShinySession {
+    child: Option<Child>,
+    /// Keeps the child's port out of the allocator while it runs.
+    lease: Option<PortLease>,
+    status: ShinyStatus,
+    /// Bumped whenever a child is attached or taken, so stale watchers and
+    /// reader threads can tell they belong to an old process.
+    generation: u64,
+    stderr_tail: VecDeque<String>,
+}
+
+impl ShinySession {
+    fn new(app_id: &str) -> Self {
+        Self {
+            child: None,
+            lease: None,
+            status: ShinyStatus::new(app_id),
+            generation: 0,
+            stderr_tail: VecDeque::new(),
+        }
+    }
+}
+
+/// Owns the R child processes, one per app id, and tracks their state.
+/// Stored in Tauri state.
+#[derive(Clone)]
+pub struct ShinyManager {
+    sessions: Arc<Mutex<HashMap<String, ShinySession>>>,
+    pidfile: Option<PidFile>,
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+fn emit_transition(app_handle: &AppHandle, from: ShinyState, status: ShinyStatus) {
+    log::info!(
+        "Shiny state [{}]: {:?} -> {:?}",
+        status.app_id,
+        from,
+        status.state
+    );
+    app_handle
+        .emit("shiny-state-changed", ShinyTransition { from, status })
+        .unwrap_or_else(|e| log::error!("Failed to emit state event: {}", e));
+}
+
+impl ShinyManager {
+    /// Creates a manager that records its children in `pidfile`, if given.
+    pub fn new(pidfile: Option<PidFile>) -> Self {
+        Self {
+            sessions: Arc::default(),
+            pidfile,
+        }
+    }
+
+    /// Runs `f` on the session for `app_id`, creating an idle one if needed.
+    fn with_session<T>(&self, app_id: &str, f: impl FnOnce(&mut ShinySession) -> T) -> T {
+        let mut sessions = self.sessions.lock().unwrap();
+        let session = sessions
+            .entry(app_id.to_string())
+            .or_insert_with(|| ShinySession::new(app_id));
+        f(session)
+    }
+
+    /// Returns the current status, first checking whether the child has exited.
+    pub fn status(&self, app_handle: &AppHandle, app_id: &str) -> ShinyStatus {
+        self.refresh(app_handle, app_id);
+        self.with_session(app_id, |session| session.status.clone())
+    }
+
+    /// Ids of apps that currently own a child process.
+    pub fn running_app_ids(&self) -> Vec<String> {
+        let sessions = self.sessions.lock().unwrap();
+        sessions
+            .iter()
+            .filter(|(_, session)| session.child.is_some())
+            .map(|(id, _)| id.clone())
+            .collect()
+    }
+
+    fn forget_pid(&self, pid: Option<u32>) {
+        if let (Some(pidfile), Some(pid)) = (&self.pidfile, pid) {
+            pidfile.remove(pid);
+        }
+    }
+
+    /// Moves `app_id` to `state` and emits `shiny-state-changed` if the state changed.
+    pub fn transition(&self, app_handle: &AppHandle, app_id: &str, state: ShinyState) {
+        let changed = self.with_session(app_id, |session| {
+            if session.status.state == state {
+                return None;
+            }
+            let from = std::mem::replace(&mut session.status.state, state);
+            if !session.status.state.is_active() {
+                session.status.url = None;
+                session.status.pid = None;
+            }
+            Some((from, session.status.clone()))
+        });
+        if let Some((from, status)) = changed {
+            emit_transition(app_handle, from, status);
+        }
+    }
+
+    /// Claims `app_id` for a launch and returns its status from before.
+    /// Fails while a start or stop is under way. Otherwise the app moves to
+    /// `Starting`, unless it is `Ready` and needs no launch. Checking and
+    /// moving happen under one lock, so concurrent starts cannot both pass.
+    pub fn begin_start(
+        &self,
+        app_handle: &AppHandle,
+        app_id: &str,
+    ) -> Result<ShinyStatus, AppError> {
+        self.refresh(app_handle, app_id);
+        let (current, claimed) = self.claim_start(app_id)?;
+        if let Some(status) = claimed {
+            emit_transition(app_handle, current.state.clone(), status);
+        }
+        Ok(current)
+    }
+
+    fn claim_start(&self, app_id: &str) -> Result<(ShinyStatus, Option<ShinyStatus>), AppError> {
+        self.with_session(app_id, |session| {
+            let current = session.status.clone();
+            match current.state {
+                ShinyState::Starting | ShinyState::Unhealthy | ShinyState::Stopping => {
+                    Err(AppError::AlreadyRunning(app_id.to_string()))
+                }
+                ShinyState::Ready => Ok((current, None)),
+                _ => {
+                    session.status.state = ShinyState::Starting;
+                    Ok((current, Some(session.status.clone())))
+                }
+            }
+        })
+    }
+
+    /// Returns an app whose launch failed before a child was attached from
+    /// `Starting` to `Idle`, so it can be started again.
+    pub fn abandon_start(&self, app_handle: &AppHandle, app_id: &str) {
+        let changed = self.with_session(app_id, |session| {
+            if session.child.is_some() || session.status.state != ShinyState::Starting {
+                return None;
+            }
+            let from = std::mem::replace(&mut session.status.state, ShinyState::Idle);
+            session.status.url = None;
+            session.status.pid = None;
+            Some((from, session.status.clone()))
+        });
+        if let Some((from, status)) = changed {
+            emit_transition(app_handle, from, status);
+        }
+    }
+
+    /// Records a freshly spawned child and returns its generation.
+    /// The state is left at `Starting`. A child still attached is stopped
+    /// rather than leaked along with its port.
+    pub fn attach(&self, app_id: &str, child: Child, lease: PortLease, url: &str) -> u64 {
+        if let Some(pidfile) = &self.pidfile {
+            pidfile.add(PidEntry {
+                app_id: app_id.to_string(),
+                pid: child.id(),
+                started_at: now_millis(),
+            });
+        }
+        let (generation, replaced) = self.with_session(app_id, |session| {
+            session.generation += 1;
+            session.stderr_tail.clear();
+            session.status.pid = Some(child.id());
+            session.status.port = Some(lease.port());
+            session.status.url = Some(url.to_string());
+            session.status.started_at = Some(now_millis());
+            session.status.exit_code = None;
+            let replaced = session.child.replace(child);
+            let old_lease = session.lease.replace(lease);
+            (session.generation, replaced.map(|child| (child, old_lease)))
+        });
+        // The old lease is dropped only once its process is gone
+        if let Some((mut old, _old_lease)) = replaced {
+            log::warn!(
+                "Shiny app '{}' still had process {} attached; stopping it",
+                app_id,
+                old.id()
+            );
+            graceful_stop(&mut old, StopSignal::Terminate, Duration::ZERO);
+            self.forget_pid(Some(old.id()));
+        }
+        generation
+    }
+
+    /// Removes the child from the manager so the caller can stop it, and
+    /// returns its port to the allocator.
+    /// Any watcher on the old child stops once it sees the new generation.
+    /// The pid stays in the pidfile until `forget_child` is called, so a
+    /// crash mid-stop still leaves a trace for the next startup sweep.
+    pub fn take_child(&self, app_id: &str) -> Option<Child> {
+        self.with_session(app_id, |session| {
+            session.generation += 1;
+            session.lease = None;
+            session.child.take()
+        })
+    }
+
+    /// Drops a stopped child's pid from the pidfile.
+    pub fn forget_child(&self, pid: u32) {
+        self.forget_pid(Some(pid));
+    }
+
+    pub fn generation(&self, app_id: &str) -> u64 {
+        self.with_session(app_id, |session| session.generation)
+    }
+
+    /// Appends a stderr line from the process of the given generation.
+    pub fn push_stderr(&self, app_id: &str, generation: u64, line: &str) {
+        self.with_session(app_id, |session| {
+            if session.generation != generation {
+                return;
+            }
+            if session.stderr_tail.len() == STDERR_TAIL_LINES {
+                session.stderr_tail.pop_front();
+            }
+            session.stderr_tail.push_back(line.to_string());
+        })
+    }
+
+    /// Increments and returns the automatic restart counter.
+    pub fn record_restart(&self, app_id: &str) -> u32 {
+        self.with_session(app_id, |session| {
+            session.status.restarts += 1;
+            session.status.restarts
+        })
+    }
+
+    pub fn reset_restarts(&self, app_id: &str) {
+        self.with_session(app_id, |session| session.status.restarts = 0)
+    }
+
+    /// Polls the child without blocking. An exit we did not ask for is
+    /// recorded as `Exited(0)` for a clean exit and `Crashed` otherwise.
+    pub fn refresh(&self, app_handle: &AppHandle, app_id: &str) {
+        let transition = self.with_session(app_id, |session| {
+            let exit = match session.child.as_mut().map(|c| c.try_wait()) {
+                Some(Ok(Some(exit))) => exit,
+                _ => return None,
+            };
+            session.child = None;
+            session.lease = None;
+            session.status.exit_code = exit.code();
+            session.status.last_stderr = session.stderr_tail.iter().cloned().collect();
+            let state = if session.status.state == ShinyState::Stopping || exit.success() {
+                ShinyState::Exited(exit.code())
+            } else {
+                ShinyState::Crashed
+            };
+            let from = std::mem::replace(&mut session.status.state, state);
+            session.status.url = None;
+            let pid = session.status.pid.take();
+            Some((pid, from, session.status.clone()))
+        });
+        if let Some((pid, from, status)) = transition {
+            self.forget_pid(pid);
+            emit_transition(app_handle, from, status);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shiny_ports::{PortAllocator, PortStrategy};
+    use std::net::{IpAddr, Ipv4Addr};
+    use std::sync::Barrier;
+    use std::thread;
+
+    #[test]
+    fn concurrent_starts_claim_the_app_once() {
+        for _ in 0..50 {
+            let manager = ShinyManager::new(None);
+            let barrier = Arc::new(Barrier::new(2));
+            let starts: Vec<_> = (0..2)
+                .map(|_| {
+                    let manager = manager.clone();
+                    let barrier = barrier.clone();
+                    thread::spawn(move || {
+                        barrier.wait();
+                        manager.claim_start("app")
+                    })
+                })
+                .collect();
+            let results: Vec<_> = starts.into_iter().map(|t| t.join().unwrap()).collect();
+
+            let claimed = results
+                .iter()
+                .filter(|r| matches!(r, Ok((_, Some(_)))))
+                .count();
+            let refused = results
+                .iter()
+                .filter(|r| matches!(r, Err(AppError::AlreadyRunning(_))))
+                .count();
+            assert_eq!((claimed, refused), (1, 1));
+            assert_eq!(
+                manager.with_session("app", |s| s.status.state.clone()),
+                ShinyState::Starting
+            );
+        }
+    }
+
+    #[test]
+    fn ready_app_is_not_claimed_again() {
+        let manager = ShinyManager::new(None);
+        manager.with_session("app", |s| s.status.state = ShinyState::Ready);
+        let (current, claimed) = manager.claim_start("app").unwrap();
+        assert_eq!(current.state, ShinyState::Ready);
+        assert!(claimed.is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn attach_stops_a_child_that_is_still_attached() {
+        use crate::shiny_process::new_process_group;
+        use std::process::Command;
+
+        fn sleeper() -> Child {
+            let mut command = Command::new("sleep");
+            command.arg("30");
+            new_process_group(&mut command);
+            command.spawn().unwrap()
+        }
+
+        let host = IpAddr::V4(Ipv4Addr::LOCALHOST);
+        let allocator = PortAllocator::default();
+        let manager = ShinyManager::new(None);
+
+        let mut first_lease = allocator.reserve(host, &PortStrategy::Ephemeral).unwrap();
+        first_lease.release_socket();
+        let first_port = first_lease.port();
+        manager.attach("app", sleeper(), first_lease, "first");
+        let first_pid = manager.with_session("app", |s| s.status.pid).unwrap();
+
+        let mut second_lease = allocator.reserve(host, &PortStrategy::Ephemeral).unwrap();
+        second_lease.release_socket();
+        manager.attach("app", sleeper(), second_lease, "second");
+
+        // The first child is gone and its port is back in the pool
+        assert!(!crate::shiny_process::kill_process_tree(first_pid));
+        allocator
+            .reserve(host, &PortStrategy::Fixed { port: first_port })
+            .unwrap();
+
+        let mut child = manager.take_child("app").unwrap();
+        graceful_stop(&mut child, StopSignal::Terminate, Duration::ZERO);
+    }
+}
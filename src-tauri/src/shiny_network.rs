@@ -0,0 +1,167 @@
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::net::{IpAddr, Ipv4Addr};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Emitter, Manager};
+
+/// File in the app log directory recording every launch exposed beyond loopback.
+const AUDIT_LOG_NAME: &str = "shiny-audit.log";
+
+/// Where the Shiny server listens, set per app under `network` in `shiny.json`.
+///
+/// Binding to anything but loopback makes the app reachable from other
+/// machines, so it takes both `lan_sharing` and `access_control`, and
+/// `session_token` off: only the loopback proxy can send the token.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct NetworkPolicy {
+    pub bind_address: IpAddr,
+    /// Explicit opt-in to a non-loopback `bind_address`.
+    pub lan_sharing: bool,
+    /// Set when the app authenticates its own users (e.g. with shinymanager).
+    pub access_control: bool,
+    /// Serve the app through a loopback proxy that requires a per-launch token.
+    pub session_token: bool,
+}
+
+impl Default for NetworkPolicy {
+    fn default() -> Self {
+        Self {
+            bind_address: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            lan_sharing: false,
+            access_control: false,
+            session_token: true,
+        }
+    }
+}
+
+/// Payload of the `shiny-lan-sharing` event.
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct LanSharingWarning<'a> {
+    app_id: &'a str,
+    address: String,
+    port: u16,
+}
+
+impl NetworkPolicy {
+    pub fn is_loopback(&self) -> bool {
+        self.bind_address.is_loopback()
+    }
+
+    /// Refuses a non-loopback bind that was not explicitly opted into, that
+    /// would serve the app without access control, or that R would refuse
+    /// to serve because it requires the session token.
+    pub fn check(&self) -> Result<(), String> {
+        if self.is_loopback() {
+            return Ok(());
+        }
+        if !self.lan_sharing {
+            return Err(format!(
+                "Refusing to bind Shiny to {}: set \"lan_sharing\": true to share the app on the network",
+                self.bind_address
+            ));
+        }
+        if !self.access_control {
+            return Err(format!(
+                "Refusing to bind Shiny to {} without access control",
+                self.bind_address
+            ));
+        }
+        if self.session_token {
+            return Err(format!(
+                "Cannot share Shiny on {} with \"session_token\" on: other machines cannot send the token. Set \"session_token\": false",
+                self.bind_address
+            ));
+        }
+        Ok(())
+    }
+
+    /// Address the app itself uses to reach the server.
+    pub fn connect_host(&self) -> IpAddr {
+        if self.bind_address.is_unspecified() {
+            match self.bind_address {
+                IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::LOCALHOST),
+                IpAddr::V6(_) => IpAddr::V6(std::net::Ipv6Addr::LOCALHOST),
+            }
+        } else {
+            self.bind_address
+        }
+    }
+
+    /// Warns the frontend and writes an audit line when a launch is reachable
+    /// from the network. Does nothing for loopback binds.
+    pub fn announce(&self, app_handle: &AppHandle, app_id: &str, port: u16) {
+        if self.is_loopback() {
+            return;
+        }
+        let address = self.bind_address.to_string();
+        log::warn!(
+            "WARNING: Shiny app '{}' is shared on the network at {}:{}",
+            app_id,
+            address,
+            port
+        );
+        app_handle
+            .emit(
+                "shiny-lan-sharing",
+                LanSharingWarning {
+                    app_id,
+                    address: address.clone(),
+                    port,
+                },
+            )
+            .unwrap_or_else(|e| log::error!("Failed to emit LAN sharing event: {}", e));
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let line = format!(
+            "{} lan-sharing app={} bind={}:{} access_control={}\n",
+            timestamp, app_id, address, port, self.access_control
+        );
+        let result = app_handle
+            .path()
+            .app_log_dir()
+            .map_err(|e| e.to_string())
+            .and_then(|dir| {
+                std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+                std::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(dir.join(AUDIT_LOG_NAME))
+                    .and_then(|mut file| file.write_all(line.as_bytes()))
+                    .map_err(|e| e.to_string())
+            });
+        if let Err(e) = result {
+            log::error!("Failed to write audit log: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lan(session_token: bool) -> NetworkPolicy {
+        NetworkPolicy {
+            bind_address: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            lan_sharing: true,
+            access_control: true,
+            session_token,
+        }
+    }
+
+    #[test]
+    fn lan_sharing_requires_session_token_off() {
+        let err = lan(true).check().unwrap_err();
+        assert!(err.contains("session_token"), "{}", err);
+        assert!(lan(false).check().is_ok());
+    }
+
+    #[test]
+    fn loopback_is_always_allowed() {
+        assert!(NetworkPolicy::default().check().is_ok());
+    }
+}
@@ -0,0 +1,146 @@
+use std::io::{BufRead, BufReader, Read};
+use std::thread;
+
+/// Something the launcher cares about, recognised in R's output.
+#[derive(Clone, Debug, PartialEq)]
+pub enum OutputSignal {
+    /// Shiny printed `Listening on http://...` and is bound.
+    Listening(String),
+    /// httpuv could not bind the port because another process holds it.
+    PortInUse,
+    /// R hit a top-level error and printed `Execution halted`.
+    Failed(String),
+}
+
+/// Fragments httpuv prints when another process holds the port, e.g.
+/// `createTcpServer: address already in use`. The
+/// `Error in initialize(value, ...) : Failed to create server` that follows
+/// is printed for any bind failure, such as an address that is not local,
+/// so it is left to end up as `Failed`.
+const PORT_IN_USE_PATTERNS: &[&str] = &["address already in use"];
+
+/// Line-by-line parser for one output stream of an R process.
+#[derive(Default)]
+pub struct OutputParser {
+    /// Error message being collected until `Execution halted`.
+    error: Option<String>,
+}
+
+impl OutputParser {
+    /// Feeds one line and returns a signal if the line completes one.
+    pub fn feed(&mut self, line: &str) -> Option<OutputSignal> {
+        let line = line.trim_end();
+
+        if let Some(url) = line.strip_prefix("Listening on ") {
+            return Some(OutputSignal::Listening(url.trim().to_string()));
+        }
+        let lower = line.to_ascii_lowercase();
+        if PORT_IN_USE_PATTERNS.iter().any(|p| lower.contains(p)) {
+            return Some(OutputSignal::PortInUse);
+        }
+        if line == "Execution halted" {
+            let message = self
+                .error
+                .take()
+                .unwrap_or_else(|| "R stopped with 'Execution halted'".to_string());
+            return Some(OutputSignal::Failed(message));
+        }
+        // `Error in library(foo) : ...` or `Error: ...`
+        if line.starts_with("Error in ") || line.starts_with("Error: ") {
+            self.error = Some(line.to_string());
+            return None;
+        }
+        // R wraps long error messages onto indented continuation lines
+        if let Some(error) = self.error.as_mut() {
+            if line.starts_with(char::is_whitespace) {
+                error.push(' ');
+                error.push_str(line.trim());
+            }
+        }
+        None
+    }
+}
+
+/// Reads `stream` line by line on a background thread.
+pub fn spawn_reader<R, F>(stream: R, mut on_line: F)
+where
+    R: Read + Send + 'static,
+    F: FnMut(&str) + Send + 'static,
+{
+    thread::spawn(move || {
+        let reader = BufReader::new(stream);
+        for line in reader.lines().map_while(Result::ok) {
+            on_line(&line);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed_all(lines: &[&str]) -> Vec<OutputSignal> {
+        let mut parser = OutputParser::default();
+        lines.iter().filter_map(|line| parser.feed(line)).collect()
+    }
+
+    #[test]
+    fn listening_line_gives_the_url() {
+        assert_eq!(
+            feed_all(&["", "Listening on http://127.0.0.1:4321\r"]),
+            [OutputSignal::Listening("http://127.0.0.1:4321".to_string())]
+        );
+    }
+
+    #[test]
+    fn wrapped_error_is_reported_when_r_halts() {
+        let signals = feed_all(&[
+            "Loading required package: shiny",
+            "Error in library(shinydashboard) : ",
+            "  there is no package called 'shinydashboard'",
+            "Calls: source ... withVisible -> eval -> eval -> library",
+            "Execution halted",
+        ]);
+        assert_eq!(
+            signals,
+            [OutputSignal::Failed(
+                "Error in library(shinydashboard) : there is no package called 'shinydashboard'"
+                    .to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn halt_without_an_error_line_still_fails() {
+        assert_eq!(
+            feed_all(&["Execution halted"]),
+            [OutputSignal::Failed(
+                "R stopped with 'Execution halted'".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn port_in_use_is_recognised() {
+        let signals = feed_all(&[
+            "createTcpServer: address already in use",
+            "Error in initialize(value, ...) : Failed to create server",
+        ]);
+        assert_eq!(signals, [OutputSignal::PortInUse]);
+    }
+
+    #[test]
+    fn other_bind_failures_are_not_port_in_use() {
+        let signals = feed_all(&[
+            "createTcpServer: address not available",
+            "Error in initialize(value, ...) : Failed to create server",
+            "Execution halted",
+        ]);
+        assert_eq!(
+            signals,
+            [OutputSignal::Failed(
+                "Error in initialize(value, ...) : Failed to create server".to_string()
+            )]
+        );
+    }
+}
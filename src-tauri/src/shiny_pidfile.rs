@@ -0,0 +1,239 @@
+use crate::shiny_process::kill_process_tree;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+
+/// File in the app data directory listing the R children we started.
+pub const PIDFILE_NAME: &str = "shiny-pids.json";
+
+/// Process names an orphaned Shiny child can show up as.
+const R_PROCESS_NAMES: &[&str] = &["R", "Rscript", "Rterm", "R.exe", "Rscript.exe", "Rterm.exe"];
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PidEntry {
+    pub app_id: String,
+    pub pid: u32,
+    pub started_at: u64,
+}
+
+/// Persistent record of live R children, so a crashed session's orphans can
+/// be found on the next start.
+#[derive(Clone, Debug)]
+pub struct PidFile {
+    path: PathBuf,
+    /// Held for each read-modify-write so concurrent starts and stops do
+    /// not drop each other's entries. Shared by clones.
+    lock: Arc<Mutex<()>>,
+}
+
+impl PidFile {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            lock: Arc::default(),
+        }
+    }
+
+    pub fn entries(&self) -> Vec<PidEntry> {
+        std::fs::read_to_string(&self.path)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    /// Replaces the file through a temporary one, so a crash mid-write
+    /// cannot leave it truncated.
+    fn write(&self, entries: &[PidEntry]) {
+        let result = if entries.is_empty() {
+            match std::fs::remove_file(&self.path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+                _ => Ok(()),
+            }
+        } else {
+            let temp = self.path.with_extension("json.tmp");
+            serde_json::to_string_pretty(entries)
+                .map_err(std::io::Error::other)
+                .and_then(|json| std::fs::write(&temp, json))
+                .and_then(|()| std::fs::rename(&temp, &self.path))
+        };
+        if let Err(e) = result {
+            log::error!("Failed to update {}: {}", self.path.display(), e);
+        }
+    }
+
+    /// Applies `f` to the entries under the lock, writing them back if it
+    /// returns true.
+    fn update(&self, f: impl FnOnce(&mut Vec<PidEntry>) -> bool) {
+        let _guard = self.lock.lock().unwrap();
+        let mut entries = self.entries();
+        if f(&mut entries) {
+            self.write(&entries);
+        }
+    }
+
+    pub fn add(&self, entry: PidEntry) {
+        self.update(|entries| {
+            entries.retain(|e| e.pid != entry.pid);
+            entries.push(entry);
+            true
+        });
+    }
+
+    pub fn remove(&self, pid: u32) {
+        self.update(|entries| {
+            let before = entries.len();
+            entries.retain(|e| e.pid != pid);
+            entries.len() != before
+        });
+    }
+
+    /// Kills R process trees left behind by a previous session and clears
+    /// the file. Pids that now belong to something other than R are skipped.
+    pub fn sweep_orphans(&self) {
+        let _guard = self.lock.lock().unwrap();
+        for entry in self.entries() {
+            match process_name(entry.pid) {
+                Some(name) if R_PROCESS_NAMES.contains(&name.as_str()) => {
+                    log::info!(
+                        "Reaping orphaned Shiny process {} ({}) for app '{}'",
+                        entry.pid,
+                        name,
+                        entry.app_id
+                    );
+                    kill_process_tree(entry.pid);
+                }
+                Some(name) => log::info!(
+                    "Pid {} from a previous session is now '{}'; leaving it alone",
+                    entry.pid,
+                    name
+                ),
+                None => {}
+            }
+        }
+        self.write(&[]);
+    }
+}
+
+/// Executable name of a running process, or `None` if it does not exist.
+#[cfg(unix)]
+fn process_name(pid: u32) -> Option<String> {
+    let output = Command::new("ps")
+        .args(["-p", &pid.to_string(), "-o", "comm="])
+        .output()
+        .ok()?;
+    let name = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    // `comm` may be a full path on macOS
+    let name = name.rsplit('/').next().unwrap_or_default().to_string();
+    (!name.is_empty()).then_some(name)
+}
+
+#[cfg(windows)]
+fn process_name(pid: u32) -> Option<String> {
+    use std::os::windows::process::CommandExt;
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+    let output = Command::new("tasklist")
+        .args(["/FI", &format!("PID eq {}", pid), "/FO", "CSV", "/NH"])
+        .creation_flags(CREATE_NO_WINDOW)
+        .output()
+        .ok()?;
+    // "Rscript.exe","1234","Console","1","12,345 K"
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let name = stdout
+        .split(',')
+        .next()?
+        .trim()
+        .trim_matches('"')
+        .to_string();
+    name.to_ascii_lowercase().ends_with(".exe").then_some(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+    use std::thread;
+
+    fn entry(pid: u32) -> PidEntry {
+        PidEntry {
+            app_id: format!("app-{}", pid),
+            pid,
+            started_at: 0,
+        }
+    }
+
+    fn pids(pidfile: &PidFile) -> Vec<u32> {
+        let mut pids: Vec<u32> = pidfile.entries().iter().map(|e| e.pid).collect();
+        pids.sort();
+        pids
+    }
+
+    #[test]
+    fn concurrent_adds_keep_every_entry() {
+        let dir = TempDir::new("pidfile-test");
+        let pidfile = PidFile::new(dir.0.join(PIDFILE_NAME));
+
+        let threads: Vec<_> = (0..8)
+            .map(|t| {
+                let pidfile = pidfile.clone();
+                thread::spawn(move || {
+                    for i in 0..20 {
+                        pidfile.add(entry(t * 100 + i));
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        let expected: Vec<u32> = (0..8)
+            .flat_map(|t| (0..20).map(move |i| t * 100 + i))
+            .collect();
+        assert_eq!(pids(&pidfile), expected);
+    }
+
+    #[test]
+    fn concurrent_adds_and_removes_only_drop_removed_entries() {
+        let dir = TempDir::new("pidfile-test");
+        let pidfile = PidFile::new(dir.0.join(PIDFILE_NAME));
+        for pid in 0..50 {
+            pidfile.add(entry(pid));
+        }
+
+        let remover = {
+            let pidfile = pidfile.clone();
+            thread::spawn(move || (0..50).step_by(2).for_each(|pid| pidfile.remove(pid)))
+        };
+        let adder = {
+            let pidfile = pidfile.clone();
+            thread::spawn(move || (100..150).for_each(|pid| pidfile.add(entry(pid))))
+        };
+        remover.join().unwrap();
+        adder.join().unwrap();
+
+        let expected: Vec<u32> = (1..50).step_by(2).chain(100..150).collect();
+        assert_eq!(pids(&pidfile), expected);
+    }
+
+    #[test]
+    fn add_replaces_entry_with_same_pid_and_remove_deletes_empty_file() {
+        let dir = TempDir::new("pidfile-test");
+        let path = dir.0.join(PIDFILE_NAME);
+        let pidfile = PidFile::new(path.clone());
+
+        pidfile.add(entry(7));
+        pidfile.add(PidEntry {
+            app_id: "other".to_string(),
+            ..entry(7)
+        });
+        let entries = pidfile.entries();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].app_id, "other");
+
+        pidfile.remove(7);
+        assert!(!path.exists());
+        assert!(!path.with_extension("json.tmp").exists());
+    }
+}
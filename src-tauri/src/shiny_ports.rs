@@ -0,0 +1,185 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, SocketAddr, TcpListener};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How long a port that R reported as taken is skipped by range allocation.
+const BUSY_COOLDOWN: Duration = Duration::from_secs(60);
+
+/// How an app picks its port, set under `ports` in `shiny.json`.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(tag = "mode", rename_all = "kebab-case")]
+pub enum PortStrategy {
+    /// Always use this port; fail if it is taken.
+    Fixed { port: u16 },
+    /// Let the OS pick a free port.
+    Ephemeral,
+    /// First free port in `start..end`.
+    Range { start: u16, end: u16 },
+}
+
+impl Default for PortStrategy {
+    fn default() -> Self {
+        PortStrategy::Range {
+            start: 3000,
+            end: 8000,
+        }
+    }
+}
+
+#[derive(Default)]
+struct PortTable {
+    /// Ports currently leased to one of our apps.
+    leased: HashSet<u16>,
+    /// Ports another process turned out to hold, with when we found out.
+    busy: HashMap<u16, Instant>,
+}
+
+/// Hands out ports to Shiny launches. Stored in Tauri state.
+#[derive(Clone, Default)]
+pub struct PortAllocator {
+    table: Arc<Mutex<PortTable>>,
+}
+
+/// A port reserved for one launch. The port returns to the pool when the
+/// lease is dropped.
+pub struct PortLease {
+    port: u16,
+    /// Holds the port bound until R is about to start.
+    listener: Option<TcpListener>,
+    table: Arc<Mutex<PortTable>>,
+}
+
+impl PortLease {
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// Releases the placeholder socket. Call immediately before spawning R
+    /// so the window in which another process can grab the port is minimal.
+    pub fn release_socket(&mut self) {
+        self.listener.take();
+    }
+}
+
+impl Drop for PortLease {
+    fn drop(&mut self) {
+        self.table.lock().unwrap().leased.remove(&self.port);
+    }
+}
+
+impl PortAllocator {
+    /// Reserves a port on `host` according to `strategy`.
+    pub fn reserve(&self, host: IpAddr, strategy: &PortStrategy) -> Result<PortLease, String> {
+        let mut table = self.table.lock().unwrap();
+        table
+            .busy
+            .retain(|_, since| since.elapsed() < BUSY_COOLDOWN);
+
+        let (port, listener) = match *strategy {
+            PortStrategy::Fixed { port } => {
+                if table.leased.contains(&port) {
+                    return Err(format!(
+                        "Port {} is already used by another Shiny app",
+                        port
+                    ));
+                }
+                let listener = TcpListener::bind(SocketAddr::new(host, port))
+                    .map_err(|e| format!("Port {} is not available: {}", port, e))?;
+                (port, listener)
+            }
+            PortStrategy::Ephemeral => {
+                // A lease whose socket was released can be handed out again
+                // by the OS before R binds it. Rejected sockets stay bound
+                // until a fresh port turns up.
+                let mut rejected = Vec::new();
+                loop {
+                    let listener = TcpListener::bind(SocketAddr::new(host, 0))
+                        .map_err(|e| format!("Could not get an ephemeral port: {}", e))?;
+                    let port = listener.local_addr().map_err(|e| e.to_string())?.port();
+                    if !table.leased.contains(&port) {
+                        break (port, listener);
+                    }
+                    if rejected.len() >= table.leased.len() {
+                        return Err("Could not get an ephemeral port".to_string());
+                    }
+                    rejected.push(listener);
+                }
+            }
+            PortStrategy::Range { start, end } => (start..end)
+                .filter(|port| !table.leased.contains(port) && !table.busy.contains_key(port))
+                .find_map(|port| {
+                    TcpListener::bind(SocketAddr::new(host, port))
+                        .ok()
+                        .map(|listener| (port, listener))
+                })
+                .ok_or_else(|| format!("No available ports in range {}-{}", start, end))?,
+        };
+
+        table.leased.insert(port);
+        Ok(PortLease {
+            port,
+            listener: Some(listener),
+            table: self.table.clone(),
+        })
+    }
+
+    /// Records that R could not bind `port` because something else holds it.
+    pub fn mark_busy(&self, port: u16) {
+        self.table.lock().unwrap().busy.insert(port, Instant::now());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    const LOCALHOST: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+    #[test]
+    fn fixed_port_already_leased_is_refused() {
+        let allocator = PortAllocator::default();
+        let mut lease = allocator
+            .reserve(LOCALHOST, &PortStrategy::Ephemeral)
+            .unwrap();
+        // Free the socket so only the lease itself stands in the way
+        lease.release_socket();
+        let fixed = PortStrategy::Fixed { port: lease.port() };
+
+        let err = allocator.reserve(LOCALHOST, &fixed).err().unwrap();
+        assert!(err.contains("already used by another Shiny app"), "{}", err);
+    }
+
+    #[test]
+    fn dropping_a_lease_frees_the_port() {
+        let allocator = PortAllocator::default();
+        let lease = allocator
+            .reserve(LOCALHOST, &PortStrategy::Ephemeral)
+            .unwrap();
+        let port = lease.port();
+        drop(lease);
+
+        let again = allocator
+            .reserve(LOCALHOST, &PortStrategy::Fixed { port })
+            .unwrap();
+        assert_eq!(again.port(), port);
+    }
+
+    #[test]
+    fn ephemeral_never_hands_out_a_leased_port() {
+        let allocator = PortAllocator::default();
+        let mut leases = Vec::new();
+        for _ in 0..50 {
+            let mut lease = allocator
+                .reserve(LOCALHOST, &PortStrategy::Ephemeral)
+                .unwrap();
+            // As the launcher does right before spawning R
+            lease.release_socket();
+            leases.push(lease);
+        }
+        let ports: HashSet<u16> = leases.iter().map(PortLease::port).collect();
+        assert_eq!(ports.len(), leases.len());
+    }
+}
@@ -0,0 +1,176 @@
+use crate::shiny_proxy::BACKEND_TOKEN_HEADER;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+use tokio::net::TcpStream;
+
+/// Per-request timeout for a single probe.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum HttpMethod {
+    Head,
+    Get,
+}
+
+/// How to tell that the Shiny server is accepting requests.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum ProbeKind {
+    /// Something accepts TCP connections on the port.
+    Tcp,
+    /// An HTTP request to `path` (e.g. `/` or `/healthz`) returns
+    /// `expect_status`, or any 2xx when unset.
+    Http {
+        #[serde(default = "default_method")]
+        method: HttpMethod,
+        #[serde(default = "default_path")]
+        path: String,
+        #[serde(default)]
+        expect_status: Option<u16>,
+    },
+}
+
+fn default_method() -> HttpMethod {
+    HttpMethod::Head
+}
+
+fn default_path() -> String {
+    "/".to_string()
+}
+
+impl Default for ProbeKind {
+    fn default() -> Self {
+        ProbeKind::Http {
+            method: default_method(),
+            path: default_path(),
+            expect_status: None,
+        }
+    }
+}
+
+/// Readiness polling settings, set per app under `readiness` in `shiny.json`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ReadinessPolicy {
+    /// Give up once this much time has passed since spawning R.
+    pub timeout_ms: u64,
+    /// Wait before the first probe.
+    pub initial_delay_ms: u64,
+    /// Each wait is the previous one times this factor.
+    pub backoff_factor: f64,
+    /// Upper bound for a single wait.
+    pub max_interval_ms: u64,
+    pub probe: ProbeKind,
+}
+
+impl Default for ReadinessPolicy {
+    fn default() -> Self {
+        Self {
+            timeout_ms: 60_000,
+            initial_delay_ms: 500,
+            backoff_factor: 1.5,
+            max_interval_ms: 5_000,
+            probe: ProbeKind::default(),
+        }
+    }
+}
+
+impl ReadinessPolicy {
+    pub fn timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout_ms)
+    }
+
+    /// Wait before probe number `attempt` (0-based).
+    pub fn interval(&self, attempt: u32) -> Duration {
+        let factor = self.backoff_factor.max(1.0).powi(attempt.min(64) as i32);
+        let ms = (self.initial_delay_ms as f64 * factor).min(self.max_interval_ms as f64);
+        Duration::from_millis(ms as u64)
+    }
+}
+
+/// Runs one probe against the server on `host:port`. HTTP probes send the
+/// session token, which R requires when one is set.
+pub async fn probe(
+    client: &Client,
+    kind: &ProbeKind,
+    host: IpAddr,
+    port: u16,
+    token: Option<&str>,
+) -> Result<(), String> {
+    let address = SocketAddr::new(host, port);
+    match kind {
+        ProbeKind::Tcp => tokio::time::timeout(PROBE_TIMEOUT, TcpStream::connect(address))
+            .await
+            .map_err(|_| "TCP connect timed out".to_string())?
+            .map(|_| ())
+            .map_err(|e| format!("TCP connect failed: {}", e)),
+        ProbeKind::Http {
+            method,
+            path,
+            expect_status,
+        } => {
+            let url = format!("http://{}/{}", address, path.trim_start_matches('/'));
+            let mut request = match method {
+                HttpMethod::Head => client.head(&url),
+                HttpMethod::Get => client.get(&url),
+            };
+            if let Some(token) = token {
+                request = request.header(BACKEND_TOKEN_HEADER, token);
+            }
+            let response = request
+                .timeout(PROBE_TIMEOUT)
+                .send()
+                .await
+                .map_err(|e| format!("{} {} failed: {}", method_name(*method), url, e))?;
+            let status = response.status();
+            let ok = match expect_status {
+                Some(expected) => status.as_u16() == *expected,
+                None => status.is_success(),
+            };
+            if ok {
+                Ok(())
+            } else {
+                Err(format!("{} returned {}", url, status))
+            }
+        }
+    }
+}
+
+fn method_name(method: HttpMethod) -> &'static str {
+    match method {
+        HttpMethod::Head => "HEAD",
+        HttpMethod::Get => "GET",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interval_grows_up_to_the_max() {
+        let policy = ReadinessPolicy {
+            initial_delay_ms: 1000,
+            backoff_factor: 2.0,
+            max_interval_ms: 5000,
+            ..Default::default()
+        };
+        let intervals: Vec<_> = (0..6)
+            .map(|attempt| policy.interval(attempt).as_millis())
+            .collect();
+        assert_eq!(intervals, [1000, 2000, 4000, 5000, 5000, 5000]);
+        assert_eq!(policy.interval(u32::MAX), Duration::from_millis(5000));
+    }
+
+    #[test]
+    fn interval_never_shrinks() {
+        let policy = ReadinessPolicy {
+            backoff_factor: 0.5,
+            ..Default::default()
+        };
+        assert_eq!(policy.interval(3), policy.interval(0));
+    }
+}
@@ -0,0 +1,160 @@
+use serde::{Deserialize, Serialize};
+use std::process::{Child, Command};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Signal sent to the process group before escalating to a kill.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum StopSignal {
+    /// SIGINT. R turns it into an interrupt, so `runApp` unwinds and runs
+    /// the app's `onStop` handlers.
+    #[default]
+    Interrupt,
+    /// SIGTERM.
+    Terminate,
+}
+
+/// How the process actually went away.
+#[derive(Clone, Copy, Debug, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum StopOutcome {
+    /// The process had already exited before we signalled it.
+    AlreadyExited,
+    /// The process exited within the grace period after the stop signal.
+    Terminated,
+    /// The grace period ran out and the process group was force-killed.
+    Killed,
+}
+
+/// Returned by `stop_r_shiny`.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StopReport {
+    pub outcome: StopOutcome,
+    pub exit_code: Option<i32>,
+    /// True if other processes in the group (callr/future workers,
+    /// Chromote) were still alive and had to be killed.
+    pub group_killed: bool,
+    pub elapsed_ms: u64,
+}
+
+/// Puts the child in its own process group so it can be signalled as a tree.
+pub fn new_process_group(command: &mut Command) {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+    }
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        const CREATE_NEW_PROCESS_GROUP: u32 = 0x0000_0200;
+        const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+        command.creation_flags(CREATE_NEW_PROCESS_GROUP | CREATE_NO_WINDOW);
+    }
+}
+
+/// Sends `signal`, waits up to `grace` for the child to exit, then kills
+/// the whole process group.
+pub fn graceful_stop(child: &mut Child, signal: StopSignal, grace: Duration) -> StopReport {
+    let started = Instant::now();
+    let pid = child.id();
+
+    if let Ok(Some(exit)) = child.try_wait() {
+        return StopReport {
+            outcome: StopOutcome::AlreadyExited,
+            exit_code: exit.code(),
+            group_killed: kill_group(pid),
+            elapsed_ms: started.elapsed().as_millis() as u64,
+        };
+    }
+
+    send_stop_signal(pid, signal);
+    let mut exit_code = None;
+    let mut outcome = StopOutcome::Killed;
+    while started.elapsed() < grace {
+        if let Ok(Some(exit)) = child.try_wait() {
+            exit_code = exit.code();
+            outcome = StopOutcome::Terminated;
+            break;
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+
+    // Always sweep the group: workers may outlive a cleanly exiting R
+    let group_killed = kill_group(pid);
+    if outcome == StopOutcome::Killed {
+        let _ = child.kill();
+        exit_code = child.wait().ok().and_then(|s| s.code());
+    }
+
+    let report = StopReport {
+        outcome,
+        exit_code,
+        group_killed,
+        elapsed_ms: started.elapsed().as_millis() as u64,
+    };
+    log::info!("Stopped process group {}: {:?}", pid, report);
+    report
+}
+
+/// Force-kills a process tree we started earlier, e.g. an orphan from a
+/// crashed session. Returns true if anything was alive.
+pub fn kill_process_tree(pid: u32) -> bool {
+    kill_group(pid)
+}
+
+#[cfg(unix)]
+fn send_stop_signal(pgid: u32, signal: StopSignal) {
+    let signal = match signal {
+        StopSignal::Interrupt => libc::SIGINT,
+        StopSignal::Terminate => libc::SIGTERM,
+    };
+    // A negative pid addresses the whole process group
+    unsafe {
+        libc::kill(-(pgid as libc::pid_t), signal);
+    }
+}
+
+/// Kills whatever is left of the group. Returns true if anything was alive.
+#[cfg(unix)]
+fn kill_group(pgid: u32) -> bool {
+    let pgid = -(pgid as libc::pid_t);
+    unsafe {
+        if libc::kill(pgid, 0) != 0 {
+            return false;
+        }
+        libc::kill(pgid, libc::SIGKILL) == 0
+    }
+}
+
+/// Windows has no signals for console-less children; ask `taskkill` to
+/// close the tree politely first.
+#[cfg(windows)]
+fn send_stop_signal(pid: u32, _signal: StopSignal) {
+    let _ = taskkill(pid, false);
+}
+
+#[cfg(windows)]
+fn kill_group(pid: u32) -> bool {
+    taskkill(pid, true)
+}
+
+#[cfg(windows)]
+fn taskkill(pid: u32, force: bool) -> bool {
+    use std::os::windows::process::CommandExt;
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+    let mut command = Command::new("taskkill");
+    command.args(["/PID", &pid.to_string(), "/T"]);
+    if force {
+        command.arg("/F");
+    }
+    command
+        .creation_flags(CREATE_NO_WINDOW)
+        .output()
+        .map(|o| o.status.success())
+        .unwrap_or(false)
+}
@@ -0,0 +1,479 @@
+use http_body_util::{combinators::BoxBody, BodyExt, Full};
+use hyper::body::{Bytes, Incoming};
+use hyper::header::{self, HeaderName, HeaderValue};
+use hyper::service::service_fn;
+use hyper::{Method, Request, Response, StatusCode, Uri};
+use hyper_util::rt::TokioIo;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::net::{TcpListener, TcpStream};
+
+/// Query parameter carrying the session token in the URL we hand out.
+pub const TOKEN_PARAM: &str = "shiny_token";
+/// Header the webview's `shiny://` handler authenticates with.
+pub const TOKEN_HEADER: &str = "x-shiny-token";
+/// Header the proxy adds to every forwarded request. Shiny refuses requests
+/// without it (see `shiny_gate.R`), so R's port cannot be used directly.
+pub const BACKEND_TOKEN_HEADER: &str = "shiny-shared-secret";
+/// Cookie the token is swapped into, scoped to the app's path prefix.
+const TOKEN_COOKIE: &str = "shiny_token";
+
+/// Served while the app's R process is down or still starting.
+const RESTARTING_PAGE: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<meta http-equiv="refresh" content="2">
+<title>Restarting…</title>
+<style>body{font-family:sans-serif;display:flex;align-items:center;justify-content:center;height:90vh;color:#444}</style>
+</head>
+<body><p>The app is restarting. This page will reload automatically.</p></body>
+</html>
+"#;
+
+type ProxyBody = BoxBody<Bytes, hyper::Error>;
+
+/// Generates a random per-session token.
+pub fn new_token() -> String {
+    rand::random::<[u8; 32]>()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Where requests for one app go.
+struct Route {
+    app_id: String,
+    /// Current R port. Updated on every (re)launch.
+    backend: SocketAddr,
+    /// Required on every request when set.
+    token: Option<Arc<str>>,
+}
+
+/// Reverse proxy on a stable loopback port in front of every Shiny app.
+///
+/// `/<app-id>/...` is forwarded to whichever port that app's R process has,
+/// so the URL the webview holds survives restarts and port changes. The
+/// port is saved in settings and reused by the next run when it is free.
+/// The session token in the URL still changes each time an app is started.
+/// Stored in Tauri state.
+#[derive(Clone)]
+pub struct ShinyProxy {
+    port: u16,
+    /// Keyed by the app id as it appears in the URL path.
+    routes: Arc<Mutex<HashMap<String, Route>>>,
+}
+
+impl ShinyProxy {
+    /// Binds the proxy on a loopback port and starts serving. Uses
+    /// `preferred_port` if it is free, otherwise any port.
+    pub async fn start(preferred_port: Option<u16>) -> Result<Self, String> {
+        let preferred = match preferred_port {
+            Some(port) => TcpListener::bind((Ipv4Addr::LOCALHOST, port))
+                .await
+                .inspect_err(|e| log::warn!("Shiny proxy port {} is not available: {}", port, e))
+                .ok(),
+            None => None,
+        };
+        let listener = match preferred {
+            Some(listener) => listener,
+            None => TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+                .await
+                .map_err(|e| format!("Failed to start Shiny proxy: {}", e))?,
+        };
+        let port = listener.local_addr().map_err(|e| e.to_string())?.port();
+        let proxy = Self {
+            port,
+            routes: Arc::default(),
+        };
+        log::info!("Shiny proxy listening on http://127.0.0.1:{}", port);
+
+        let server = proxy.clone();
+        tauri::async_runtime::spawn(async move {
+            loop {
+                let stream = match listener.accept().await {
+                    Ok((stream, _)) => stream,
+                    Err(e) => {
+                        log::error!("Shiny proxy accept failed: {}", e);
+                        continue;
+                    }
+                };
+                let server = server.clone();
+                tauri::async_runtime::spawn(async move {
+                    let service = service_fn(move |req| {
+                        let server = server.clone();
+                        async move { Ok::<_, Infallible>(server.handle(req).await) }
+                    });
+                    if let Err(e) = hyper::server::conn::http1::Builder::new()
+                        .serve_connection(TokioIo::new(stream), service)
+                        .with_upgrades()
+                        .await
+                    {
+                        log::error!("Shiny proxy connection error: {}", e);
+                    }
+                });
+            }
+        });
+        Ok(proxy)
+    }
+
+    /// Points `app_id` at `backend`. A token is generated the first time the
+    /// route is opened and kept until `close`, so automatic restarts do not
+    /// invalidate the page already open. Returns the token, if any.
+    pub fn open(&self, app_id: &str, backend: SocketAddr, session_token: bool) -> Option<String> {
+        let mut routes = self.routes.lock().unwrap();
+        let route = routes
+            .entry(encode_segment(app_id))
+            .or_insert_with(|| Route {
+                app_id: app_id.to_string(),
+                backend,
+                token: None,
+            });
+        route.backend = backend;
+        if !session_token {
+            route.token = None;
+        } else if route.token.is_none() {
+            route.token = Some(new_token().into());
+        }
+        route.token.as_deref().map(str::to_string)
+    }
+
+    /// Removes the route for `app_id`, discarding its token.
+    pub fn close(&self, app_id: &str) {
+        self.routes.lock().unwrap().remove(&encode_segment(app_id));
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// Token guarding the app whose prefix `path` starts with.
+    pub fn token_for_path(&self, path: &str) -> Option<String> {
+        let segment = path.trim_start_matches('/').split('/').next()?;
+        let routes = self.routes.lock().unwrap();
+        routes.get(segment)?.token.as_deref().map(str::to_string)
+    }
+
+    /// Stable URL of `app_id`, carrying its token.
+    pub fn url(&self, app_id: &str) -> String {
+        let segment = encode_segment(app_id);
+        let token = self
+            .routes
+            .lock()
+            .unwrap()
+            .get(&segment)
+            .and_then(|route| route.token.clone());
+        match token {
+            Some(token) => format!(
+                "http://127.0.0.1:{}/{}/?{}={}",
+                self.port, segment, TOKEN_PARAM, token
+            ),
+            None => format!("http://127.0.0.1:{}/{}/", self.port, segment),
+        }
+    }
+
+    async fn handle(&self, req: Request<Incoming>) -> Response<ProxyBody> {
+        let started = Instant::now();
+        let method = req.method().clone();
+        let path = req.uri().path().to_string();
+        let (app_id, response) = self.route(req).await;
+        log::info!(
+            "[proxy] {} {} {} -> {} ({} ms)",
+            app_id.as_deref().unwrap_or("-"),
+            method,
+            path,
+            response.status().as_u16(),
+            started.elapsed().as_millis()
+        );
+        response
+    }
+
+    async fn route(&self, mut req: Request<Incoming>) -> (Option<String>, Response<ProxyBody>) {
+        let path = req.uri().path();
+        let rest = path.trim_start_matches('/');
+        let segment = rest.split('/').next().unwrap_or_default().to_string();
+
+        let found = self
+            .routes
+            .lock()
+            .unwrap()
+            .get(&segment)
+            .map(|route| (route.app_id.clone(), route.backend, route.token.clone()));
+        let Some((app_id, backend, token)) = found else {
+            return (
+                None,
+                text_response(StatusCode::NOT_FOUND, "No such Shiny app"),
+            );
+        };
+        let prefix = format!("/{}/", segment);
+
+        // Shiny uses relative asset URLs, so the prefix needs its trailing slash
+        if !path.starts_with(&prefix) {
+            let location = match req.uri().query() {
+                Some(query) => format!("{}?{}", prefix, query),
+                None => prefix,
+            };
+            return (Some(app_id), redirect(&location, None));
+        }
+
+        if let Some(token) = &token {
+            match query_param(req.uri(), TOKEN_PARAM) {
+                Some(candidate) if !token_matches(candidate, token) => {
+                    let response = text_response(StatusCode::FORBIDDEN, "Invalid session token");
+                    return (Some(app_id), response);
+                }
+                // WebSocket clients cannot follow the cookie redirect, so
+                // they keep the token in the URL
+                Some(_) if req.headers().contains_key(header::UPGRADE) => {
+                    match strip_token(req.uri()).parse::<Uri>() {
+                        Ok(uri) => *req.uri_mut() = uri,
+                        Err(_) => {
+                            let response = text_response(StatusCode::BAD_REQUEST, "Invalid URL");
+                            return (Some(app_id), response);
+                        }
+                    }
+                }
+                Some(_) => {
+                    // Swap the token in the URL for a cookie so it does not linger in history
+                    let cookie = format!(
+                        "{}={}; Path={}; HttpOnly; SameSite=Strict",
+                        TOKEN_COOKIE, token, prefix
+                    );
+                    let location = strip_token(req.uri());
+                    return (Some(app_id), redirect(&location, Some(cookie)));
+                }
+                None if !has_token_cookie(&req, token) && !has_token_header(&req, token) => {
+                    let response = text_response(StatusCode::FORBIDDEN, "Missing session token");
+                    return (Some(app_id), response);
+                }
+                None => {}
+            }
+        }
+
+        let wants_html = req.method() == Method::GET
+            && req
+                .headers()
+                .get(header::ACCEPT)
+                .and_then(|v| v.to_str().ok())
+                .is_some_and(|accept| accept.contains("text/html"));
+        let response = match forward(req, backend, &prefix, token.as_deref()).await {
+            Ok(response) => response,
+            Err(e) => {
+                log::warn!(
+                    "Shiny proxy could not reach '{}' at {}: {}",
+                    app_id,
+                    backend,
+                    e
+                );
+                if wants_html {
+                    restarting_response()
+                } else {
+                    text_response(StatusCode::SERVICE_UNAVAILABLE, "Shiny app is restarting")
+                }
+            }
+        };
+        (Some(app_id), response)
+    }
+}
+
+/// Sends the request to R on a fresh connection with the app prefix removed.
+/// WebSocket upgrades are spliced through once both sides have switched
+/// protocols.
+async fn forward(
+    mut req: Request<Incoming>,
+    backend: SocketAddr,
+    prefix: &str,
+    token: Option<&str>,
+) -> Result<Response<ProxyBody>, String> {
+    let stream = TcpStream::connect(backend)
+        .await
+        .map_err(|e| e.to_string())?;
+    let (mut sender, connection) = hyper::client::conn::http1::handshake(TokioIo::new(stream))
+        .await
+        .map_err(|e| e.to_string())?;
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = connection.with_upgrades().await {
+            log::error!("Shiny proxy backend connection error: {}", e);
+        }
+    });
+
+    let path_and_query = req
+        .uri()
+        .path_and_query()
+        .map(|pq| pq.as_str())
+        .unwrap_or("/");
+    let stripped = format!("/{}", &path_and_query[prefix.len()..]);
+    *req.uri_mut() = stripped.parse::<Uri>().map_err(|e| e.to_string())?;
+
+    let client_upgrade = req
+        .headers()
+        .contains_key(header::UPGRADE)
+        .then(|| hyper::upgrade::on(&mut req));
+    let headers = req.headers_mut();
+    if let Some(host) = headers.get(header::HOST).cloned() {
+        headers.insert(HeaderName::from_static("x-forwarded-host"), host);
+    }
+    headers.insert(
+        HeaderName::from_static("x-forwarded-proto"),
+        HeaderValue::from_static("http"),
+    );
+    headers.insert(
+        HeaderName::from_static("x-forwarded-prefix"),
+        HeaderValue::from_str(prefix.trim_end_matches('/')).map_err(|e| e.to_string())?,
+    );
+    if let Some(token) = token {
+        headers.insert(
+            BACKEND_TOKEN_HEADER,
+            HeaderValue::from_str(token).map_err(|e| e.to_string())?,
+        );
+    }
+
+    let mut response = sender.send_request(req).await.map_err(|e| e.to_string())?;
+
+    if response.status() == StatusCode::SWITCHING_PROTOCOLS {
+        if let Some(client_upgrade) = client_upgrade {
+            let backend_upgrade = hyper::upgrade::on(&mut response);
+            tauri::async_runtime::spawn(async move {
+                match tokio::try_join!(client_upgrade, backend_upgrade) {
+                    Ok((client, backend)) => {
+                        let _ = tokio::io::copy_bidirectional(
+                            &mut TokioIo::new(client),
+                            &mut TokioIo::new(backend),
+                        )
+                        .await;
+                    }
+                    Err(e) => log::error!("Shiny proxy WebSocket upgrade failed: {}", e),
+                }
+            });
+        }
+    }
+    Ok(response.map(|body| body.boxed()))
+}
+
+/// Percent-encodes everything but RFC 3986 unreserved characters, so an app
+/// id is usable as a single path segment.
+fn encode_segment(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+fn query_param<'a>(uri: &'a Uri, name: &str) -> Option<&'a str> {
+    uri.query()?.split('&').find_map(|pair| {
+        pair.split_once('=')
+            .filter(|(key, _)| *key == name)
+            .map(|(_, value)| value)
+    })
+}
+
+/// Set by in-process clients such as the `shiny://` scheme handler.
+fn has_token_header(req: &Request<Incoming>, token: &str) -> bool {
+    req.headers()
+        .get(TOKEN_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| token_matches(value, token))
+}
+
+fn has_token_cookie(req: &Request<Incoming>, token: &str) -> bool {
+    req.headers()
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|cookies| cookies.split(';'))
+        .filter_map(|cookie| cookie.trim().split_once('='))
+        .any(|(name, value)| name == TOKEN_COOKIE && token_matches(value, token))
+}
+
+/// Compares tokens without bailing out at the first differing byte.
+fn token_matches(candidate: &str, token: &str) -> bool {
+    candidate.len() == token.len()
+        && candidate
+            .bytes()
+            .zip(token.bytes())
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// Path and query of `uri` with the token parameter removed.
+fn strip_token(uri: &Uri) -> String {
+    let rest: Vec<&str> = uri
+        .query()
+        .unwrap_or_default()
+        .split('&')
+        .filter(|pair| !pair.is_empty() && pair.split('=').next() != Some(TOKEN_PARAM))
+        .collect();
+    if rest.is_empty() {
+        uri.path().to_string()
+    } else {
+        format!("{}?{}", uri.path(), rest.join("&"))
+    }
+}
+
+fn full_body(bytes: Bytes) -> ProxyBody {
+    Full::new(bytes).map_err(|never| match never {}).boxed()
+}
+
+fn redirect(location: &str, cookie: Option<String>) -> Response<ProxyBody> {
+    let mut builder = Response::builder()
+        .status(StatusCode::FOUND)
+        .header(header::LOCATION, location);
+    if let Some(cookie) = cookie {
+        builder = builder.header(header::SET_COOKIE, cookie);
+    }
+    builder.body(full_body(Bytes::new())).unwrap()
+}
+
+fn restarting_response() -> Response<ProxyBody> {
+    Response::builder()
+        .status(StatusCode::SERVICE_UNAVAILABLE)
+        .header(header::CONTENT_TYPE, "text/html; charset=utf-8")
+        .header(header::RETRY_AFTER, "2")
+        .header(header::CACHE_CONTROL, "no-store")
+        .body(full_body(Bytes::from_static(RESTARTING_PAGE.as_bytes())))
+        .unwrap()
+}
+
+fn text_response(status: StatusCode, message: &'static str) -> Response<ProxyBody> {
+    Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, "text/plain; charset=utf-8")
+        .body(full_body(Bytes::from_static(message.as_bytes())))
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn start_reuses_the_preferred_port_when_free() {
+        let free = std::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let port = free.local_addr().unwrap().port();
+        drop(free);
+
+        let proxy = ShinyProxy::start(Some(port)).await.unwrap();
+        assert_eq!(proxy.port(), port);
+        assert!(proxy
+            .url("app")
+            .starts_with(&format!("http://127.0.0.1:{}/", port)));
+    }
+
+    #[tokio::test]
+    async fn start_falls_back_when_the_preferred_port_is_taken() {
+        let taken = std::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let port = taken.local_addr().unwrap().port();
+
+        let proxy = ShinyProxy::start(Some(port)).await.unwrap();
+        assert_ne!(proxy.port(), port);
+        assert_ne!(proxy.port(), 0);
+    }
+}
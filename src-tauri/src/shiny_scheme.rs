@@ -0,0 +1,113 @@
+use crate::shiny_proxy::{ShinyProxy, TOKEN_HEADER, TOKEN_PARAM};
+use std::sync::OnceLock;
+use tauri::http::{header, Request, Response, StatusCode};
+use tauri::{AppHandle, Manager, UriSchemeResponder};
+
+/// Custom protocol serving Shiny apps inside the webview: `shiny://localhost/<app-id>/`
+/// (`http://shiny.localhost/<app-id>/` on Windows).
+pub const SCHEME: &str = "shiny";
+
+/// Headers not copied between the webview request and the proxy.
+const HOP_HEADERS: &[header::HeaderName] = &[
+    header::HOST,
+    header::CONNECTION,
+    header::CONTENT_LENGTH,
+    header::TRANSFER_ENCODING,
+    header::ACCEPT_ENCODING,
+];
+
+fn client() -> &'static reqwest::Client {
+    static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+    CLIENT.get_or_init(|| {
+        reqwest::Client::builder()
+            // Redirects go back to the webview so they resolve against shiny://
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .expect("Failed to build HTTP client")
+    })
+}
+
+/// Handles one `shiny://` request by forwarding it to the Shiny proxy.
+pub fn handle(app_handle: &AppHandle, request: Request<Vec<u8>>, responder: UriSchemeResponder) {
+    let proxy = app_handle.state::<ShinyProxy>().inner().clone();
+    tauri::async_runtime::spawn(async move {
+        let response = forward(&proxy, request).await.unwrap_or_else(|e| {
+            log::error!("shiny:// request failed: {}", e);
+            Response::builder()
+                .status(StatusCode::BAD_GATEWAY)
+                .header(header::CONTENT_TYPE, "text/plain; charset=utf-8")
+                .body(e.into_bytes())
+                .unwrap()
+        });
+        responder.respond(response);
+    });
+}
+
+async fn forward(
+    proxy: &ShinyProxy,
+    request: Request<Vec<u8>>,
+) -> Result<Response<Vec<u8>>, String> {
+    let (parts, body) = request.into_parts();
+    let path_and_query = parts
+        .uri
+        .path_and_query()
+        .map(|pq| pq.as_str())
+        .unwrap_or("/");
+    let token = proxy.token_for_path(parts.uri.path());
+
+    let mut outgoing = client()
+        .request(
+            parts.method.clone(),
+            format!("http://127.0.0.1:{}{}", proxy.port(), path_and_query),
+        )
+        .body(body);
+    for (name, value) in parts.headers.iter() {
+        if !HOP_HEADERS.contains(name) {
+            outgoing = outgoing.header(name, value);
+        }
+    }
+    // We are in-process, so authenticate with the header instead of a cookie
+    if let Some(token) = &token {
+        outgoing = outgoing.header(TOKEN_HEADER, token);
+    }
+
+    let upstream = outgoing.send().await.map_err(|e| e.to_string())?;
+    let status = upstream.status();
+    let headers = upstream.headers().clone();
+    let is_html = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("text/html"));
+    let mut body = upstream.bytes().await.map_err(|e| e.to_string())?.to_vec();
+    if is_html {
+        body = inject_socket_override(body, proxy.port(), token.as_deref());
+    }
+
+    let mut response = Response::builder().status(status);
+    for (name, value) in headers.iter() {
+        if !HOP_HEADERS.contains(name) {
+            response = response.header(name, value);
+        }
+    }
+    response.body(body).map_err(|e| e.to_string())
+}
+
+/// Custom schemes cannot carry WebSockets, so point Shiny's socket at the
+/// proxy instead. Runs after shiny.js has loaded and before Shiny connects.
+fn inject_socket_override(body: Vec<u8>, port: u16, token: Option<&str>) -> Vec<u8> {
+    let html = String::from_utf8_lossy(&body);
+    let Some(head_end) = html.find("</head>") else {
+        return body;
+    };
+    let query = token
+        .map(|token| format!("?{}={}", TOKEN_PARAM, token))
+        .unwrap_or_default();
+    let script = format!(
+        r#"<script>(function(){{var base="ws://127.0.0.1:{port}"+location.pathname.replace(/[^/]*$/,"");window.Shiny=window.Shiny||{{}};window.Shiny.createSocket=function(){{return new WebSocket(base+"websocket/{query}");}};}})();</script>"#
+    );
+    let mut injected = String::with_capacity(html.len() + script.len());
+    injected.push_str(&html[..head_end]);
+    injected.push_str(&script);
+    injected.push_str(&html[head_end..]);
+    injected.into_bytes()
+}
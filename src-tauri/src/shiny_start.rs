@@ -0,0 +1,102 @@
+use crate::error::AppError;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+use tokio::sync::watch;
+
+/// In-flight start operations, keyed by operation id. Stored in Tauri state.
+#[derive(Default)]
+pub struct StartOperations {
+    next_id: AtomicU64,
+    cancels: Mutex<HashMap<String, (String, watch::Sender<bool>)>>,
+}
+
+/// Handle a launch uses to report progress and observe cancellation.
+pub struct StartOp {
+    pub id: String,
+    pub app_id: String,
+    cancel: watch::Receiver<bool>,
+}
+
+/// Payload of the `shiny-start-progress` event.
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct StartProgress<'a> {
+    op_id: &'a str,
+    app_id: &'a str,
+    message: String,
+}
+
+impl StartOperations {
+    /// Registers a new operation for `app_id`.
+    pub fn begin(&self, app_id: &str) -> StartOp {
+        let n = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let id = format!("{}-{}", app_id, n);
+        let (sender, receiver) = watch::channel(false);
+        self.cancels
+            .lock()
+            .unwrap()
+            .insert(id.clone(), (app_id.to_string(), sender));
+        StartOp {
+            id,
+            app_id: app_id.to_string(),
+            cancel: receiver,
+        }
+    }
+
+    pub fn finish(&self, op_id: &str) {
+        self.cancels.lock().unwrap().remove(op_id);
+    }
+
+    /// Signals the operation to stop. Returns false if it is not running.
+    pub fn cancel(&self, op_id: &str) -> bool {
+        match self.cancels.lock().unwrap().get(op_id) {
+            Some((_, sender)) => sender.send(true).is_ok(),
+            None => false,
+        }
+    }
+
+    /// Signals every operation starting `app_id` to stop.
+    pub fn cancel_app(&self, app_id: &str) {
+        for (op_app_id, sender) in self.cancels.lock().unwrap().values() {
+            if op_app_id == app_id {
+                let _ = sender.send(true);
+            }
+        }
+    }
+}
+
+impl StartOp {
+    pub fn is_cancelled(&self) -> bool {
+        *self.cancel.borrow()
+    }
+
+    /// Sleeps for `duration`, returning early with `Err` on cancellation.
+    pub async fn sleep(&mut self, duration: Duration) -> Result<(), AppError> {
+        if self.is_cancelled() {
+            return Err(AppError::Cancelled);
+        }
+        tokio::select! {
+            _ = tokio::time::sleep(duration) => Ok(()),
+            _ = self.cancel.wait_for(|cancelled| *cancelled) => Err(AppError::Cancelled),
+        }
+    }
+
+    /// Emits a `shiny-start-progress` event for this operation.
+    pub fn progress(&self, app_handle: &AppHandle, message: String) {
+        log::info!("[{}] {}", self.id, message);
+        app_handle
+            .emit(
+                "shiny-start-progress",
+                StartProgress {
+                    op_id: &self.id,
+                    app_id: &self.app_id,
+                    message,
+                },
+            )
+            .unwrap_or_else(|e| log::error!("Failed to emit progress event: {}", e));
+    }
+}
@@ -0,0 +1,129 @@
+use crate::error::AppError;
+use crate::r_shiny::launch_shiny;
+use crate::shiny_config::ShinyConfig;
+use crate::shiny_manager::{ShinyManager, ShinyState};
+use crate::shiny_proxy::ShinyProxy;
+use crate::shiny_start::StartOperations;
+use crate::shiny_window;
+use serde::Serialize;
+use std::thread;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Payload of the `shiny-restarted` event.
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ShinyRestarted {
+    app_id: String,
+    url: String,
+    attempt: u32,
+    previous_exit_code: Option<i32>,
+}
+
+/// Watches the child of `generation` and applies the restart policy once it
+/// exits on its own. The watcher ends quietly if the child is stopped or
+/// replaced through the manager.
+pub fn spawn_watcher(app_handle: AppHandle, app_id: String, generation: u64, config: ShinyConfig) {
+    thread::spawn(move || {
+        let manager = app_handle.state::<ShinyManager>().inner().clone();
+        loop {
+            thread::sleep(POLL_INTERVAL);
+            if manager.generation(&app_id) != generation {
+                return;
+            }
+            let status = manager.status(&app_handle, &app_id);
+            if status.state.is_active() {
+                continue;
+            }
+
+            log::warn!(
+                "Shiny app '{}' exited unexpectedly with code {:?}. Last stderr:\n{}",
+                app_id,
+                status.exit_code,
+                status.last_stderr.join("\n")
+            );
+            let crashed = status.state == ShinyState::Crashed;
+            restart(
+                &app_handle,
+                &manager,
+                &app_id,
+                &config,
+                crashed,
+                status.exit_code,
+            );
+            return;
+        }
+    });
+}
+
+fn restart(
+    app_handle: &AppHandle,
+    manager: &ShinyManager,
+    app_id: &str,
+    config: &ShinyConfig,
+    mut crashed: bool,
+    exit_code: Option<i32>,
+) {
+    let mut generation = manager.generation(app_id);
+    loop {
+        let attempt = manager.status(app_handle, app_id).restarts + 1;
+        let Some(delay) = config.restart.next_delay(crashed, attempt) else {
+            log::info!(
+                "Restart policy {:?} exhausted; leaving Shiny stopped",
+                config.restart
+            );
+            // Nothing will come back behind the route, so do not leave the
+            // window waiting on it
+            app_handle.state::<ShinyProxy>().close(app_id);
+            shiny_window::close(app_handle, app_id);
+            app_handle
+                .emit("shiny-stopped", app_id)
+                .unwrap_or_else(|e| log::error!("Failed to emit stopped event: {}", e));
+            return;
+        };
+        manager.record_restart(app_id);
+        log::info!("Restarting Shiny in {} ms (restart {})", delay, attempt);
+        thread::sleep(Duration::from_millis(delay));
+
+        // The user started or stopped the app while we were waiting
+        if manager.generation(app_id) != generation {
+            return;
+        }
+        match manager.begin_start(app_handle, app_id) {
+            Ok(current) if current.state != ShinyState::Ready => {}
+            _ => return,
+        }
+
+        // Registered like a user start so the restart can be cancelled too
+        let operations = app_handle.state::<StartOperations>();
+        let op = operations.begin(app_id);
+        let op_id = op.id.clone();
+        let result = tauri::async_runtime::block_on(launch_shiny(app_handle, op));
+        operations.finish(&op_id);
+
+        match result {
+            Ok(url) => {
+                app_handle
+                    .emit(
+                        "shiny-restarted",
+                        ShinyRestarted {
+                            app_id: app_id.to_string(),
+                            url,
+                            attempt,
+                            previous_exit_code: exit_code,
+                        },
+                    )
+                    .unwrap_or_else(|e| log::error!("Failed to emit restarted event: {}", e));
+                return;
+            }
+            Err(AppError::Cancelled) => return,
+            Err(e) => {
+                log::error!("Restart {} failed: {}", attempt, e);
+                crashed = true;
+                generation = manager.generation(app_id);
+            }
+        }
+    }
+}
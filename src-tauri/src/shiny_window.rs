@@ -0,0 +1,114 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use tauri::{AppHandle, Manager, WebviewUrl, WebviewWindow, WebviewWindowBuilder, WindowEvent};
+
+/// File in the app data directory remembering each app window's geometry.
+const GEOMETRY_FILE: &str = "shiny-windows.json";
+
+/// Size and position in logical pixels.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+struct Geometry {
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+}
+
+/// Window label for an app. Labels only allow alphanumerics and `-/:_`.
+fn label(app_id: &str) -> String {
+    let id: String = app_id
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("shiny-{}", id)
+}
+
+fn geometry_path(app_handle: &AppHandle) -> Option<PathBuf> {
+    app_handle
+        .path()
+        .app_data_dir()
+        .ok()
+        .map(|dir| dir.join(GEOMETRY_FILE))
+}
+
+fn load_geometry(app_handle: &AppHandle) -> HashMap<String, Geometry> {
+    geometry_path(app_handle)
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+fn save_geometry(window: &WebviewWindow) {
+    let app_handle = window.app_handle();
+    let geometry = (|| {
+        let scale = window.scale_factor().ok()?;
+        let position = window.outer_position().ok()?.to_logical::<f64>(scale);
+        let size = window.inner_size().ok()?.to_logical::<f64>(scale);
+        Some(Geometry {
+            x: position.x,
+            y: position.y,
+            width: size.width,
+            height: size.height,
+        })
+    })();
+    let (Some(geometry), Some(path)) = (geometry, geometry_path(app_handle)) else {
+        return;
+    };
+    let mut all = load_geometry(app_handle);
+    all.insert(window.label().to_string(), geometry);
+    let result = serde_json::to_string_pretty(&all)
+        .map_err(std::io::Error::other)
+        .and_then(|json| std::fs::write(&path, json));
+    if let Err(e) = result {
+        log::error!(
+            "Failed to save window geometry to {}: {}",
+            path.display(),
+            e
+        );
+    }
+}
+
+/// Shows the app's window, creating it at `url` if it is not open yet.
+pub fn open(app_handle: &AppHandle, app_id: &str, url: &str) -> Result<(), String> {
+    let label = label(app_id);
+    if let Some(window) = app_handle.get_webview_window(&label) {
+        window.unminimize().map_err(|e| e.to_string())?;
+        window.show().map_err(|e| e.to_string())?;
+        return window.set_focus().map_err(|e| e.to_string());
+    }
+
+    let url = url
+        .parse()
+        .map_err(|e| format!("Invalid Shiny URL {}: {}", url, e))?;
+    let mut builder = WebviewWindowBuilder::new(app_handle, &label, WebviewUrl::External(url))
+        .title(format!("{} - Shiny", app_id));
+    builder = match load_geometry(app_handle).get(&label) {
+        Some(g) => builder.inner_size(g.width, g.height).position(g.x, g.y),
+        None => builder.inner_size(1200.0, 800.0).center(),
+    };
+    let window = builder.build().map_err(|e| e.to_string())?;
+
+    let handle = window.clone();
+    window.on_window_event(move |event| {
+        if let WindowEvent::CloseRequested { .. } = event {
+            save_geometry(&handle);
+        }
+    });
+    Ok(())
+}
+
+/// Closes the app's window, if open.
+pub fn close(app_handle: &AppHandle, app_id: &str) {
+    if let Some(window) = app_handle.get_webview_window(&label(app_id)) {
+        window
+            .close()
+            .unwrap_or_else(|e| log::error!("Failed to close window for '{}': {}", app_id, e));
+    }
+}
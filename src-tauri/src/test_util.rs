@@ -0,0 +1,33 @@
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+/// Temporary directory removed on drop.
+pub struct TempDir(pub PathBuf);
+
+impl TempDir {
+    pub fn new(prefix: &str) -> Self {
+        static NEXT: AtomicU32 = AtomicU32::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "{}-{}-{}",
+            prefix,
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Minisign key pair and signature made for `signed_payload()` only.
+pub const PUBKEY: &str = "dW50cnVzdGVkIGNvbW1lbnQ6IG1pbmlzaWduIHB1YmxpYyBrZXkgODZEMENERjFDMTMyMzU0QQpSV1JLTlRMQjhjM1Focnhsa0hhQWVZVWM1OS9VTHBycTQ4OWZUak9uczNuenhEdFJ1c09oeDIzTgo=";
+pub const SIGNATURE: &str = "dW50cnVzdGVkIGNvbW1lbnQ6IHNpZ25hdHVyZSBmcm9tIG1pbmlzaWduIHNlY3JldCBrZXkKUlVSS05UTEI4YzNRaG9mZEM2UitEeXhpUzlseDBsMnI4Sk1pNUpUMk5ZRGkyYXU5VE9yZkg1NWp1S1BPa1NUR0REcjV6SUVrbS9VTVFkVVMxVmY3Z0UxSFJYVjBLWHIrNUFvPQp0cnVzdGVkIGNvbW1lbnQ6IHRpbWVzdGFtcDoxNzAwMDAwMDAwCWZpbGU6dGVzdC1pbnN0YWxsZXIuYmluCk0wM1Fncm8xSytHMG5jQ3RKZkdEYkVPYkhBM2pyeFcxVFNVbnF2d0R2SG1QLzY4TVo5RWY0clBsejdoL1A4RXVNZVM2OEpmMkZjUW9PdjgrVmNZVUFnPT0K";
+
+pub fn signed_payload() -> Vec<u8> {
+    (0..100_000u32).map(|i| (i % 251) as u8).collect()
+}
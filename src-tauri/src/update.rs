@@ -1,141 +1,740 @@
+use crate::error::AppError;
+use crate::settings;
+use crate::update_channel::{self, UpdateChannel};
+use crate::update_download;
+use crate::update_installer;
+use crate::update_pending::{self, PendingUpdate};
+use crate::update_rollback::{self, Rollback};
+use crate::update_rollout;
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
-use tauri::{AppHandle, Manager}; // Add Manager trait here
-use tauri_plugin_updater::UpdaterExt;
+use std::time::{Duration, Instant};
+use tauri::ipc::Channel;
+use tauri::{AppHandle, Emitter, Manager}; // Add Manager trait here
+use tauri_plugin_updater::{Update, UpdaterBuilder, UpdaterExt};
+use time::format_description::well_known::Rfc3339;
+use tokio::sync::watch;
+use ts_rs::TS;
+
+/// Cache directory for partly downloaded installers.
+const STAGING_DIR: &str = "updates";
+/// Cache directory for the installer of the running version while it is
+/// fetched for rollback.
+const RUNNING_STAGING_DIR: &str = "rollback";
+/// Launches of a newly installed version that may fail to report back
+/// healthy before the previous version is reinstalled automatically.
+const MAX_FAILED_LAUNCHES: u32 = 2;
 
 pub struct UpdateState {
     pub progress: Arc<Mutex<UpdateProgress>>,
+    /// Release whose installer `download_update` stored.
+    pub downloaded: Mutex<Option<Update>>,
+    /// Signals the running `download_update`, if any, to stop.
+    pub cancel: Mutex<Option<watch::Sender<bool>>>,
+    /// Set while the installer of the running version is fetched.
+    pub keeping_running: AtomicBool,
 }
 
+/// Snapshot of the running download, returned by `get_update_progress` for
+/// callers that missed the channel messages.
+#[derive(Clone, Debug, Default, Serialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
 pub struct UpdateProgress {
     pub downloading: bool,
     pub percent: f64,
+    #[ts(type = "number")]
     pub downloaded: u64,
+    #[ts(type = "number | null")]
     pub total: Option<u64>,
+    #[ts(type = "number")]
+    pub bytes_per_sec: u64,
+    /// Seconds left at the current rate.
+    #[ts(type = "number | null")]
+    pub eta: Option<u64>,
     pub complete: bool,
+    pub cancelled: bool,
     pub error: Option<String>,
 }
 
-impl Default for UpdateProgress {
-    fn default() -> Self {
-        Self {
-            downloading: false,
-            percent: 0.0,
-            downloaded: 0,
-            total: None,
-            complete: false,
-            error: None,
-        }
+/// An available update, returned by `check_for_updates`.
+#[derive(Clone, Debug, Serialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct UpdateInfo {
+    pub version: String,
+    pub current_version: String,
+    /// Release date from `latest.json`, RFC 3339.
+    pub date: Option<String>,
+    /// Release notes.
+    pub body: Option<String>,
+    pub download_url: String,
+    /// Installer size in bytes, if the server reports it.
+    #[ts(type = "number | null")]
+    pub size: Option<u64>,
+}
+
+/// Updater set up for the channel chosen in settings. Built per check so a
+/// channel change applies straight away.
+fn updater(app_handle: &AppHandle) -> Result<UpdaterBuilder, AppError> {
+    let channel = settings::load(app_handle).update_channel;
+    Ok(app_handle
+        .updater_builder()
+        .endpoints(update_channel::endpoints(app_handle, channel)?)?)
+}
+
+/// Checks the channel for an update, leaving out a release that was rolled
+/// back from or whose rollout does not include this install yet.
+async fn check(app_handle: &AppHandle) -> Result<Option<Update>, AppError> {
+    let Some(update) = updater(app_handle)?.build()?.check().await? else {
+        return Ok(None);
+    };
+    if Rollback::open(app_handle)?.load().rejected.as_ref() == Some(&update.version) {
+        log::info!(
+            "Update {} was rolled back from, skipping it",
+            update.version
+        );
+        return Ok(None);
+    }
+    let install_id = update_rollout::install_id(app_handle)?;
+    if !update_rollout::is_eligible(&update.raw_json, &update.version, &install_id) {
+        log::info!(
+            "Update {} is not rolled out to this install",
+            update.version
+        );
+        return Ok(None);
     }
+    Ok(Some(update))
 }
 
-/// Checks for updates but does not install them.
+/// Asks the download server for the installer size without downloading it.
+async fn download_size(url: &tauri::Url) -> Option<u64> {
+    let response = reqwest::Client::new()
+        .head(url.clone())
+        .timeout(Duration::from_secs(10))
+        .send()
+        .await
+        .ok()?;
+    if !response.status().is_success() {
+        return None;
+    }
+    response
+        .headers()
+        .get(reqwest::header::CONTENT_LENGTH)?
+        .to_str()
+        .ok()?
+        .parse()
+        .ok()
+}
+
+/// Checks for updates but does not install them. Returns `null` when the
+/// app is up to date.
 #[tauri::command]
-pub async fn check_for_updates(app_handle: AppHandle) -> Result<String, String> {
-    let updater = app_handle.updater().map_err(|e| e.to_string())?;
-
-    match updater.check().await {
-        Ok(update) => {
-            if let Some(update) = update {
-                let update_info = serde_json::json!({
-                    "available": true,
-                    "version": update.version,
-                    "body": update.body,
-                    "downloadUrl": update.download_url
+pub async fn check_for_updates(app_handle: AppHandle) -> Result<Option<UpdateInfo>, AppError> {
+    let Some(update) = check(&app_handle).await? else {
+        return Ok(None);
+    };
+
+    Ok(Some(UpdateInfo {
+        size: download_size(&update.download_url).await,
+        version: update.version,
+        current_version: update.current_version,
+        date: update.date.and_then(|date| date.format(&Rfc3339).ok()),
+        body: update.body,
+        download_url: update.download_url.to_string(),
+    }))
+}
+
+/// Message sent over the channel passed to `download_update`.
+#[derive(Clone, Debug, Serialize, TS)]
+#[serde(
+    tag = "event",
+    content = "data",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+#[ts(export)]
+pub enum DownloadEvent {
+    /// The server answered. `total` is the size, if it sent one;
+    /// `resumed_from` is how much an interrupted earlier download had staged.
+    Started {
+        #[ts(type = "number | null")]
+        total: Option<u64>,
+        #[ts(type = "number")]
+        resumed_from: u64,
+    },
+    Progress {
+        #[ts(type = "number")]
+        downloaded: u64,
+        #[ts(type = "number | null")]
+        total: Option<u64>,
+        #[ts(type = "number")]
+        bytes_per_sec: u64,
+        /// Seconds left at the current rate.
+        #[ts(type = "number | null")]
+        eta: Option<u64>,
+    },
+    /// The download was verified and stored.
+    Finished,
+    Failed {
+        error: String,
+    },
+    /// Stopped by `cancel_update_download`; nothing was kept.
+    Cancelled,
+}
+
+/// Minimum time between two `Progress` messages.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Turns download callbacks into `DownloadEvent`s and keeps the
+/// `UpdateState` snapshot in step for anyone polling instead.
+struct DownloadTracker {
+    progress: Arc<Mutex<UpdateProgress>>,
+    channel: Channel<DownloadEvent>,
+    started: Option<Instant>,
+    /// Bytes staged before this session, left out of the rate.
+    resumed_from: u64,
+    last_sent: Option<Instant>,
+}
+
+impl DownloadTracker {
+    fn new(progress: Arc<Mutex<UpdateProgress>>, channel: Channel<DownloadEvent>) -> Self {
+        *progress.lock().unwrap() = UpdateProgress {
+            downloading: true,
+            ..Default::default()
+        };
+        Self {
+            progress,
+            channel,
+            started: None,
+            resumed_from: 0,
+            last_sent: None,
+        }
+    }
+
+    fn send(&self, event: DownloadEvent) {
+        self.channel
+            .send(event)
+            .unwrap_or_else(|e| log::error!("Failed to send download event: {}", e));
+    }
+
+    fn step(&mut self, step: update_download::Progress) {
+        let content_length = step.total;
+        let started = match self.started {
+            Some(started) => started,
+            None => {
+                self.send(DownloadEvent::Started {
+                    total: content_length,
+                    resumed_from: step.resumed_from,
                 });
-                Ok(serde_json::to_string(&update_info).unwrap())
-            } else {
-                Ok(r#"{"available": false}"#.to_string())
+                self.resumed_from = step.resumed_from;
+                *self.started.insert(Instant::now())
+            }
+        };
+
+        let event = {
+            let mut progress = self.progress.lock().unwrap();
+            progress.downloaded = step.received;
+            progress.total = content_length;
+            if let Some(total) = content_length.filter(|total| *total > 0) {
+                progress.percent = (progress.downloaded as f64 / total as f64) * 100.0;
+            }
+            let elapsed = started.elapsed().as_secs_f64();
+            if elapsed > 0.0 {
+                let fetched = progress.downloaded.saturating_sub(self.resumed_from);
+                progress.bytes_per_sec = (fetched as f64 / elapsed) as u64;
             }
+            progress.eta = content_length
+                .filter(|_| progress.bytes_per_sec > 0)
+                .map(|total| total.saturating_sub(progress.downloaded) / progress.bytes_per_sec);
+
+            let now = Instant::now();
+            let done = content_length.is_some_and(|total| progress.downloaded >= total);
+            if !done && self.last_sent.is_some_and(|t| now - t < PROGRESS_INTERVAL) {
+                return;
+            }
+            self.last_sent = Some(now);
+            log::debug!(
+                "Downloaded {} of {} bytes ({:.1}%)",
+                progress.downloaded,
+                content_length.unwrap_or(0),
+                progress.percent
+            );
+            DownloadEvent::Progress {
+                downloaded: progress.downloaded,
+                total: progress.total,
+                bytes_per_sec: progress.bytes_per_sec,
+                eta: progress.eta,
+            }
+        };
+        self.send(event);
+    }
+
+    fn finish(&self) {
+        {
+            let mut progress = self.progress.lock().unwrap();
+            progress.complete = true;
+            progress.percent = 100.0;
+            progress.eta = Some(0);
         }
-        Err(e) => Err(format!("Failed to check for updates: {}", e)),
+        log::info!("Download finished");
+        self.send(DownloadEvent::Finished);
+    }
+
+    fn fail(&self, error: AppError) -> AppError {
+        {
+            let mut progress = self.progress.lock().unwrap();
+            progress.downloading = false;
+            progress.error = Some(error.to_string());
+        }
+        self.send(DownloadEvent::Failed {
+            error: error.to_string(),
+        });
+        error
+    }
+
+    fn cancel(&self) {
+        *self.progress.lock().unwrap() = UpdateProgress {
+            cancelled: true,
+            ..Default::default()
+        };
+        log::info!("Download cancelled");
+        self.send(DownloadEvent::Cancelled);
+    }
+}
+
+/// HTTP client for installer downloads, honouring the updater's timeout and
+/// proxy settings.
+fn download_client(update: &Update) -> Result<reqwest::Client, AppError> {
+    let mut builder = reqwest::Client::builder();
+    if let Some(timeout) = update.timeout {
+        builder = builder.timeout(timeout);
+    }
+    if let Some(proxy) = &update.proxy {
+        let proxy =
+            reqwest::Proxy::all(proxy.as_str()).map_err(|e| AppError::Internal(e.to_string()))?;
+        builder = builder.proxy(proxy);
+    }
+    builder
+        .build()
+        .map_err(|e| AppError::Internal(e.to_string()))
+}
+
+/// Public key from the updater section of `tauri.conf.json`.
+fn updater_pubkey(app_handle: &AppHandle) -> Result<String, AppError> {
+    app_handle
+        .config()
+        .plugins
+        .0
+        .get("updater")
+        .and_then(|updater| updater.get("pubkey"))
+        .and_then(|pubkey| pubkey.as_str())
+        .map(str::to_string)
+        .ok_or_else(|| AppError::UpdateFailed {
+            details: "No updater pubkey configured".to_string(),
+        })
+}
+
+/// Checks installer bytes against the release's signature and the optional
+/// `sha256` of its `latest.json` entry.
+fn verify(app_handle: &AppHandle, update: &Update, bytes: &[u8]) -> Result<(), AppError> {
+    let sha256 = update_download::manifest_sha256(&update.raw_json, update.download_url.as_str());
+    update_download::verify(
+        bytes,
+        &update.signature,
+        &updater_pubkey(app_handle)?,
+        sha256.as_deref(),
+    )
+}
+
+/// Installer bytes of the pending update, verified again since they have
+/// been sitting on disk.
+fn read_verified(
+    app_handle: &AppHandle,
+    update: &Update,
+    pending: &PendingUpdate,
+) -> Result<Vec<u8>, AppError> {
+    let bytes = update_pending::read(app_handle, pending)?;
+    if let Err(e) = verify(app_handle, update, &bytes) {
+        update_pending::clear(app_handle);
+        return Err(e);
     }
+    Ok(bytes)
 }
 
-/// Downloads and installs the update.
+/// Downloads the update and stores the verified installer without
+/// installing it, reporting progress on `on_event`. Install it with
+/// `install_downloaded_update` or `install_on_exit`.
 #[tauri::command]
-pub async fn download_and_install_update(app_handle: AppHandle) -> Result<(), String> {
+pub async fn download_update(
+    app_handle: AppHandle,
+    on_event: Channel<DownloadEvent>,
+) -> Result<PendingUpdate, AppError> {
     let state = app_handle.state::<UpdateState>();
-    let progress_clone = state.progress.clone();
-
+    let (cancel, cancelled) = watch::channel(false);
     {
-        let mut progress = progress_clone.lock().unwrap();
-        progress.downloading = true;
-        progress.percent = 0.0;
-        progress.downloaded = 0;
-        progress.complete = false;
-        progress.error = None;
+        let mut slot = state.cancel.lock().unwrap();
+        if slot.is_some() {
+            return Err(AppError::UpdateInProgress);
+        }
+        *slot = Some(cancel);
     }
+    let result = download(&app_handle, on_event, cancelled).await;
+    *state.cancel.lock().unwrap() = None;
+    result
+}
+
+async fn download(
+    app_handle: &AppHandle,
+    on_event: Channel<DownloadEvent>,
+    mut cancelled: watch::Receiver<bool>,
+) -> Result<PendingUpdate, AppError> {
+    let state = app_handle.state::<UpdateState>();
+    let tracker = Arc::new(Mutex::new(DownloadTracker::new(
+        state.progress.clone(),
+        on_event,
+    )));
+    let fail = |error: AppError| tracker.lock().unwrap().fail(error);
 
-    let updater = app_handle.updater().map_err(|e| e.to_string())?;
-    let update = match updater.check().await {
+    let update = match check(app_handle).await {
         Ok(Some(update)) => update,
-        Ok(None) => {
-            let mut progress = progress_clone.lock().unwrap();
-            progress.error = Some("No update available".to_string());
-            return Err("No update available".to_string());
-        }
-        Err(e) => {
-            let mut progress = progress_clone.lock().unwrap();
-            progress.error = Some(e.to_string());
-            return Err(e.to_string());
-        }
+        Ok(None) => return Err(fail(AppError::NoUpdateAvailable)),
+        Err(e) => return Err(fail(e)),
     };
 
-    let progress_for_progress = progress_clone.clone();
-    let progress_for_complete = progress_clone.clone();
-
-    match update
-        .download_and_install(
-            move |chunk_length, content_length| {
-                let mut progress = progress_for_progress.lock().unwrap();
-                progress.downloaded += chunk_length as u64;
-                progress.total = content_length;
-
-                if let Some(total) = content_length {
-                    if total > 0 {
-                        progress.percent = (progress.downloaded as f64 / total as f64) * 100.0;
-                    }
-                }
-
-                println!(
-                    "Downloaded {} of {} bytes ({:.1}%)",
-                    progress.downloaded,
-                    content_length.unwrap_or(0),
-                    progress.percent
-                );
-            },
-            move || {
-                let mut progress = progress_for_complete.lock().unwrap();
-                progress.complete = true;
-                progress.percent = 100.0;
-                println!("Download finished");
-            },
-        )
-        .await
-    {
-        Ok(_) => Ok(()),
-        Err(e) => {
-            let mut progress = progress_clone.lock().unwrap();
-            progress.error = Some(e.to_string());
-            Err(e.to_string())
+    let staging = app_handle
+        .path()
+        .app_cache_dir()
+        .map_err(|e| fail(e.into()))?
+        .join(STAGING_DIR)
+        .join(format!("{}.part", update.version));
+    let client = download_client(&update).map_err(fail)?;
+    let fetch = update_download::fetch(
+        &client,
+        &update.download_url,
+        &update.headers,
+        &staging,
+        |step| tracker.lock().unwrap().step(step),
+    );
+    let fetched = tokio::select! {
+        fetched = fetch => Some(fetched),
+        _ = cancelled.wait_for(|cancelled| *cancelled) => None,
+    };
+    let Some(fetched) = fetched else {
+        // Dropping the fetch closed the connection and the file
+        update_download::discard(&staging).await;
+        tracker.lock().unwrap().cancel();
+        app_handle
+            .emit("update-download-cancelled", &update.version)
+            .unwrap_or_else(|e| log::error!("Failed to emit cancelled event: {}", e));
+        return Err(AppError::Cancelled);
+    };
+    let bytes = fetched.map_err(fail)?;
+    if let Err(e) = verify(app_handle, &update, &bytes) {
+        // Corrupt or tampered with: the next attempt starts from scratch
+        update_download::discard(&staging).await;
+        return Err(fail(e));
+    }
+    let pending = update_pending::save(app_handle, &update.version, &bytes).map_err(fail)?;
+    update_download::discard(&staging).await;
+    *state.downloaded.lock().unwrap() = Some(update.clone());
+    tracker.lock().unwrap().finish();
+
+    // Not part of the download the user is waiting on
+    let app_handle = app_handle.clone();
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = keep_running_installer(&app_handle, &client, &update).await {
+            log::warn!(
+                "Could not keep the installer of the running version; updating to {} cannot be rolled back: {}",
+                update.version,
+                e
+            );
         }
+    });
+    Ok(pending)
+}
+
+/// Fetches and keeps the signed installer of the running version if the
+/// updater did not install it, so the update can still be rolled back. The
+/// manifest has to list it under `releases`, see `ListedRelease`.
+async fn keep_running_installer(
+    app_handle: &AppHandle,
+    client: &reqwest::Client,
+    update: &Update,
+) -> Result<(), AppError> {
+    let state = app_handle.state::<UpdateState>();
+    if state.keeping_running.swap(true, Ordering::AcqRel) {
+        return Ok(());
     }
+    let result = fetch_running_installer(app_handle, client, update).await;
+    state.keeping_running.store(false, Ordering::Release);
+    result
 }
 
-/// Retrieves the update progress as JSON.
+async fn fetch_running_installer(
+    app_handle: &AppHandle,
+    client: &reqwest::Client,
+    update: &Update,
+) -> Result<(), AppError> {
+    let rollback = Rollback::open(app_handle)?;
+    if rollback.has_installer(rollback.running()) {
+        return Ok(());
+    }
+    let release = update_rollback::listed_release(
+        &update.raw_json,
+        update.download_url.as_str(),
+        rollback.running(),
+    )
+    .ok_or_else(|| AppError::UpdateFailed {
+        details: format!("The manifest does not list {}", rollback.running()),
+    })?;
+    // Kept apart from `STAGING_DIR`, whose fetches discard other files
+    let staging = app_handle
+        .path()
+        .app_cache_dir()?
+        .join(RUNNING_STAGING_DIR)
+        .join(format!("{}.part", rollback.running()));
+    let bytes =
+        update_download::fetch(client, &release.url, &update.headers, &staging, |_| {}).await?;
+    update_download::discard(&staging).await;
+    update_download::verify(
+        &bytes,
+        &release.signature,
+        &updater_pubkey(app_handle)?,
+        release.sha256.as_deref(),
+    )?;
+    rollback.keep_running(&release.signature, &bytes)
+}
+
+/// Stops the running `download_update` and deletes what it downloaded so
+/// far. Emits `update-download-cancelled` once it has stopped.
 #[tauri::command]
-pub fn get_update_progress(app_handle: AppHandle) -> Result<String, String> {
+pub fn cancel_update_download(app_handle: AppHandle) -> Result<(), AppError> {
     let state = app_handle.state::<UpdateState>();
-    let progress = state.progress.lock().unwrap();
-
-    let progress_json = serde_json::json!({
-        "downloading": progress.downloading,
-        "percent": progress.percent,
-        "downloaded": progress.downloaded,
-        "total": progress.total,
-        "complete": progress.complete,
-        "error": progress.error
+    let cancel = state.cancel.lock().unwrap();
+    match cancel.as_ref() {
+        Some(sender) => {
+            sender.send_replace(true);
+            Ok(())
+        }
+        None => Err(AppError::NoUpdateDownload),
+    }
+}
+
+/// The `Update` for the pending download. After a restart it comes from a
+/// fresh check, which must still offer the same version.
+async fn pending_update(
+    app_handle: &AppHandle,
+    pending: &PendingUpdate,
+) -> Result<Update, AppError> {
+    let state = app_handle.state::<UpdateState>();
+    if let Some(update) = state.downloaded.lock().unwrap().clone() {
+        if update.version == pending.version {
+            return Ok(update);
+        }
+    }
+
+    // A paused rollout also stops a download from being installed
+    match check(app_handle).await? {
+        Some(update) if update.version == pending.version => {
+            *state.downloaded.lock().unwrap() = Some(update.clone());
+            Ok(update)
+        }
+        // Superseded or already installed
+        _ => {
+            update_pending::clear(app_handle);
+            Err(AppError::NoUpdateAvailable)
+        }
+    }
+}
+
+/// Installs the downloaded update now. On Windows the installer closes the
+/// app; elsewhere the frontend should relaunch afterwards.
+#[tauri::command]
+pub async fn install_downloaded_update(app_handle: AppHandle) -> Result<(), AppError> {
+    let pending = update_pending::load(&app_handle).ok_or(AppError::NoUpdateAvailable)?;
+    let update = pending_update(&app_handle, &pending).await?;
+    let bytes = read_verified(&app_handle, &update, &pending)?;
+    install(&app_handle, &update, &bytes)?;
+    update_pending::clear(&app_handle);
+    Ok(())
+}
+
+/// Installs verified installer bytes, keeping them so this release can be
+/// rolled back to after the next update.
+fn install(app_handle: &AppHandle, update: &Update, bytes: &[u8]) -> Result<(), AppError> {
+    Rollback::open(app_handle)?.record_install(&update.version, &update.signature, bytes)?;
+    update.install(bytes)?;
+    Ok(())
+}
+
+/// Chooses whether the downloaded update installs when the app quits.
+#[tauri::command]
+pub fn install_on_exit(app_handle: AppHandle, enabled: bool) -> Result<PendingUpdate, AppError> {
+    update_pending::set_install_on_exit(&app_handle, enabled)
+}
+
+/// Returns the downloaded update waiting to be installed, if any.
+#[tauri::command]
+pub fn get_pending_update(app_handle: AppHandle) -> Option<PendingUpdate> {
+    update_pending::load(&app_handle)
+}
+
+/// Looks up the `Update` for a download left from a previous session, so
+/// it can be installed on exit without a network round trip. Called at
+/// startup.
+pub(crate) fn restore_pending(app_handle: &AppHandle) {
+    let Some(pending) = update_pending::load(app_handle) else {
+        return;
+    };
+    if pending.version == app_handle.package_info().version.to_string() {
+        // Installed since it was downloaded
+        update_pending::clear(app_handle);
+        return;
+    }
+    let app_handle = app_handle.clone();
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = pending_update(&app_handle, &pending).await {
+            log::warn!("Pending update {} is not usable: {}", pending.version, e);
+        }
+    });
+}
+
+/// Installs the pending update if the user asked for it to happen on exit.
+/// Called when the app exits.
+pub(crate) fn install_pending_on_exit(app_handle: &AppHandle) {
+    let Some(pending) = update_pending::load(app_handle).filter(|p| p.install_on_exit) else {
+        return;
+    };
+    let update = app_handle
+        .state::<UpdateState>()
+        .downloaded
+        .lock()
+        .unwrap()
+        .clone()
+        .filter(|update| update.version == pending.version);
+    let Some(update) = update else {
+        // Not resolved this session (offline?); keep it for the next exit
+        log::warn!(
+            "Update {} could not be confirmed with the server; installing on a later exit",
+            pending.version
+        );
+        return;
+    };
+    let result = read_verified(app_handle, &update, &pending).and_then(|bytes| {
+        log::info!("Installing update {} on exit", pending.version);
+        install(app_handle, &update, &bytes)
+    });
+    match result {
+        Ok(()) => update_pending::clear(app_handle),
+        Err(e) => log::error!(
+            "Failed to install update {} on exit: {}",
+            pending.version,
+            e
+        ),
+    }
+}
+
+/// Reinstalls the release the current one replaced from its saved
+/// installer, and stops offering the current one. Works offline.
+fn rollback(app_handle: &AppHandle) -> Result<(), AppError> {
+    let pubkey = updater_pubkey(app_handle)?;
+    Rollback::open(app_handle)?.roll_back(&pubkey, |previous, bytes| {
+        update_pending::clear(app_handle);
+        update_installer::install(app_handle, &previous.version, bytes)
+    })?;
+    Ok(())
+}
+
+/// Reinstalls the previous version and restarts into it. On Windows the
+/// installer closes the app instead.
+#[tauri::command]
+pub async fn rollback_update(app_handle: AppHandle) -> Result<(), AppError> {
+    rollback(&app_handle)?;
+    app_handle.restart()
+}
+
+/// Returns the version `rollback_update` would reinstall, if any.
+#[tauri::command]
+pub fn get_rollback_version(app_handle: AppHandle) -> Option<String> {
+    Rollback::open(&app_handle)
+        .ok()?
+        .load()
+        .previous
+        .map(|previous| previous.version)
+}
+
+/// Marks a newly installed version good. The frontend calls this once it
+/// has loaded.
+#[tauri::command]
+pub fn mark_update_healthy(app_handle: AppHandle) -> Result<(), AppError> {
+    Rollback::open(&app_handle)?.mark_healthy()
+}
+
+/// Health check for the first runs of a newly installed version. Rolls
+/// back once it has failed to report healthy `MAX_FAILED_LAUNCHES` times.
+/// Called at startup.
+pub(crate) fn check_launch(app_handle: &AppHandle) {
+    let Some(failed) = Rollback::open(app_handle)
+        .ok()
+        .and_then(|rollback| rollback.begin_launch())
+    else {
+        return;
+    };
+    if failed < MAX_FAILED_LAUNCHES {
+        return;
+    }
+    log::error!(
+        "Version {} failed to launch {} times, rolling back",
+        app_handle.package_info().version,
+        failed
+    );
+    let app_handle = app_handle.clone();
+    tauri::async_runtime::spawn(async move {
+        match rollback(&app_handle) {
+            Ok(()) => app_handle.restart(),
+            Err(e) => log::error!("Rollback failed: {}", e),
+        }
     });
+}
 
-    Ok(serde_json::to_string(&progress_json).unwrap())
+/// Returns the release channel the updater follows.
+#[tauri::command]
+pub fn get_update_channel(app_handle: AppHandle) -> UpdateChannel {
+    settings::load(&app_handle).update_channel
+}
+
+/// Switches the updater to `channel` and saves the choice. An update
+/// downloaded from the previous channel is dropped.
+#[tauri::command]
+pub fn set_update_channel(app_handle: AppHandle, channel: UpdateChannel) -> Result<(), AppError> {
+    let state = app_handle.state::<UpdateState>();
+    if state.cancel.lock().unwrap().is_some() {
+        return Err(AppError::UpdateInProgress);
+    }
+    let mut settings = settings::load(&app_handle);
+    if settings.update_channel == channel {
+        return Ok(());
+    }
+    settings.update_channel = channel;
+    settings::save(&app_handle, &settings)?;
+    update_pending::clear(&app_handle);
+    *state.downloaded.lock().unwrap() = None;
+    log::info!("Update channel set to {:?}", channel);
+    Ok(())
+}
+
+/// Returns this install's id, for adding it to a release's `allowlist`.
+#[tauri::command]
+pub fn get_install_id(app_handle: AppHandle) -> Result<String, AppError> {
+    update_rollout::install_id(&app_handle)
+}
+
+/// Retrieves the update progress.
+#[tauri::command]
+pub fn get_update_progress(app_handle: AppHandle) -> UpdateProgress {
+    let state = app_handle.state::<UpdateState>();
+    let progress = state.progress.lock().unwrap().clone();
+    progress
 }
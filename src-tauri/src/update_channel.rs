@@ -0,0 +1,60 @@
+use crate::error::AppError;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Url};
+use ts_rs::TS;
+
+/// Manifest name in the endpoints of `tauri.conf.json`, which is the stable
+/// channel's.
+const STABLE_MANIFEST: &str = "latest.json";
+
+/// Release channel the updater follows. Each has its own manifest next to
+/// `latest.json`, e.g. `latest-beta.json`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize, TS)]
+#[serde(rename_all = "lowercase")]
+#[ts(export)]
+pub enum UpdateChannel {
+    #[default]
+    Stable,
+    Beta,
+    Nightly,
+}
+
+impl UpdateChannel {
+    fn manifest(self) -> &'static str {
+        match self {
+            UpdateChannel::Stable => STABLE_MANIFEST,
+            UpdateChannel::Beta => "latest-beta.json",
+            UpdateChannel::Nightly => "latest-nightly.json",
+        }
+    }
+}
+
+/// The configured updater endpoints, pointed at `channel`'s manifest.
+pub fn endpoints(app_handle: &AppHandle, channel: UpdateChannel) -> Result<Vec<Url>, AppError> {
+    let configured = app_handle
+        .config()
+        .plugins
+        .0
+        .get("updater")
+        .and_then(|updater| updater.get("endpoints"))
+        .and_then(|endpoints| endpoints.as_array())
+        .ok_or_else(|| AppError::UpdateFailed {
+            details: "No updater endpoints configured".to_string(),
+        })?;
+
+    configured
+        .iter()
+        .filter_map(|endpoint| endpoint.as_str())
+        .map(|endpoint| {
+            let Some(base) = endpoint.strip_suffix(STABLE_MANIFEST) else {
+                return Err(AppError::UpdateFailed {
+                    details: format!("Endpoint {} does not end in {}", endpoint, STABLE_MANIFEST),
+                });
+            };
+            let url = format!("{}{}", base, channel.manifest());
+            Url::parse(&url).map_err(|e| AppError::UpdateFailed {
+                details: format!("{}: {}", url, e),
+            })
+        })
+        .collect()
+}
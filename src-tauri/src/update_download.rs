@@ -0,0 +1,668 @@
+use crate::error::AppError;
+use base64::Engine;
+use minisign_verify::{PublicKey, Signature};
+use reqwest::header::{self, HeaderMap, HeaderValue};
+use reqwest::{StatusCode, Url};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::fs::{self, OpenOptions};
+use tokio::io::AsyncWriteExt;
+
+/// Attempts per `fetch`; each one resumes where the previous one stopped.
+const MAX_ATTEMPTS: u32 = 3;
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// How far a staged download has got.
+#[derive(Clone, Copy, Debug)]
+pub struct Progress {
+    /// Bytes already staged when the current request started.
+    pub resumed_from: u64,
+    pub received: u64,
+    pub total: Option<u64>,
+}
+
+/// Identifies the file a staged download belongs to, so a resume never
+/// splices together two different files.
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct Validator {
+    url: String,
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+impl Validator {
+    /// Value for `If-Range`: the server only honours the range if the file
+    /// is unchanged.
+    fn if_range(&self) -> Option<&str> {
+        self.etag.as_deref().or(self.last_modified.as_deref())
+    }
+}
+
+enum AttemptError {
+    /// Worth another attempt from wherever the staged file now ends.
+    Retry(AppError),
+    Fatal(AppError),
+}
+
+fn network(e: reqwest::Error) -> AttemptError {
+    AttemptError::Retry(AppError::NetworkUnavailable {
+        details: e.to_string(),
+    })
+}
+
+fn io_error(path: &Path, e: std::io::Error) -> AppError {
+    AppError::Internal(format!("{}: {}", path.display(), e))
+}
+
+fn validator_path(staging: &Path) -> PathBuf {
+    let mut path = OsString::from(staging.as_os_str());
+    path.push(".json");
+    PathBuf::from(path)
+}
+
+async fn read_validator(staging: &Path) -> Option<Validator> {
+    let contents = fs::read_to_string(validator_path(staging)).await.ok()?;
+    serde_json::from_str(&contents).ok()
+}
+
+/// Deletes a staged download and its validator.
+pub async fn discard(staging: &Path) {
+    for path in [staging.to_path_buf(), validator_path(staging)] {
+        if let Err(e) = fs::remove_file(&path).await {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                log::error!("Failed to remove {}: {}", path.display(), e);
+            }
+        }
+    }
+}
+
+/// Deletes anything else staged next to `staging`, e.g. an older release.
+async fn discard_others(staging: &Path) {
+    let (Some(dir), Some(name)) = (staging.parent(), staging.file_name()) else {
+        return;
+    };
+    let Ok(mut entries) = fs::read_dir(dir).await else {
+        return;
+    };
+    let validator = validator_path(staging);
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let path = entry.path();
+        if path.file_name() != Some(name) && path != validator {
+            let _ = fs::remove_file(&path).await;
+        }
+    }
+}
+
+fn header_string(headers: &HeaderMap, name: header::HeaderName) -> Option<String> {
+    headers
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string)
+}
+
+/// Start and total size from `Content-Range: bytes <start>-<end>/<total>`.
+fn content_range(headers: &HeaderMap) -> Option<(u64, Option<u64>)> {
+    let value = headers.get(header::CONTENT_RANGE)?.to_str().ok()?;
+    let (range, total) = value.strip_prefix("bytes ")?.split_once('/')?;
+    let (start, _) = range.split_once('-')?;
+    Some((start.parse().ok()?, total.parse().ok()))
+}
+
+/// Downloads `url` into the file at `staging` and returns its contents.
+///
+/// Whatever an interrupted earlier call left at `staging` is resumed with a
+/// `Range` request, guarded by `If-Range` so a changed file starts over.
+/// Connection failures are retried a few times before giving up; the staged
+/// bytes stay on disk for the next call either way.
+pub async fn fetch(
+    client: &reqwest::Client,
+    url: &Url,
+    headers: &HeaderMap,
+    staging: &Path,
+    mut on_progress: impl FnMut(Progress),
+) -> Result<Vec<u8>, AppError> {
+    if let Some(dir) = staging.parent() {
+        fs::create_dir_all(dir)
+            .await
+            .map_err(|e| io_error(dir, e))?;
+    }
+    discard_others(staging).await;
+
+    let mut attempt = 1;
+    loop {
+        match fetch_once(client, url, headers, staging, &mut on_progress).await {
+            Ok(()) => return fs::read(staging).await.map_err(|e| io_error(staging, e)),
+            Err(AttemptError::Retry(e)) if attempt < MAX_ATTEMPTS => {
+                log::error!(
+                    "Update download attempt {} failed: {}. Resuming...",
+                    attempt,
+                    e.details().unwrap_or_default()
+                );
+                attempt += 1;
+                tokio::time::sleep(RETRY_DELAY).await;
+            }
+            Err(AttemptError::Retry(e) | AttemptError::Fatal(e)) => return Err(e),
+        }
+    }
+}
+
+async fn fetch_once(
+    client: &reqwest::Client,
+    url: &Url,
+    headers: &HeaderMap,
+    staging: &Path,
+    on_progress: &mut impl FnMut(Progress),
+) -> Result<(), AttemptError> {
+    let validator = read_validator(staging)
+        .await
+        .filter(|v| v.url == url.as_str());
+    let staged = match (&validator, fs::metadata(staging).await) {
+        (Some(_), Ok(metadata)) => metadata.len(),
+        _ => 0,
+    };
+
+    let mut request = client.get(url.clone()).headers(headers.clone()).header(
+        header::ACCEPT,
+        HeaderValue::from_static("application/octet-stream"),
+    );
+    if staged > 0 {
+        request = request.header(header::RANGE, format!("bytes={}-", staged));
+        if let Some(if_range) = validator.as_ref().and_then(Validator::if_range) {
+            request = request.header(header::IF_RANGE, if_range);
+        }
+    }
+    let mut response = request.send().await.map_err(network)?;
+
+    let status = response.status();
+    let range = content_range(response.headers());
+    let (resumed_from, total) = match status {
+        StatusCode::PARTIAL_CONTENT if range.is_some_and(|(start, _)| start == staged) => {
+            let total = range
+                .and_then(|(_, total)| total)
+                .or_else(|| response.content_length().map(|len| staged + len));
+            (staged, total)
+        }
+        StatusCode::OK => (0, response.content_length()),
+        StatusCode::RANGE_NOT_SATISFIABLE | StatusCode::PARTIAL_CONTENT => {
+            // Staged more than the file holds, or a range we did not ask for
+            discard(staging).await;
+            return Err(AttemptError::Retry(AppError::UpdateFailed {
+                details: format!("{} rejected the resume range", url),
+            }));
+        }
+        status => {
+            return Err(AttemptError::Fatal(AppError::UpdateFailed {
+                details: format!("{} returned {}", url, status),
+            }));
+        }
+    };
+
+    let mut file = if resumed_from > 0 {
+        OpenOptions::new()
+            .append(true)
+            .open(staging)
+            .await
+            .map_err(|e| AttemptError::Fatal(io_error(staging, e)))?
+    } else {
+        // Fresh start: remember which file this is before writing any of it
+        let validator = Validator {
+            url: url.to_string(),
+            etag: header_string(response.headers(), header::ETAG),
+            last_modified: header_string(response.headers(), header::LAST_MODIFIED),
+        };
+        let json = serde_json::to_string(&validator)
+            .map_err(|e| AttemptError::Fatal(AppError::Internal(e.to_string())))?;
+        let path = validator_path(staging);
+        fs::write(&path, json)
+            .await
+            .map_err(|e| AttemptError::Fatal(io_error(&path, e)))?;
+        fs::File::create(staging)
+            .await
+            .map_err(|e| AttemptError::Fatal(io_error(staging, e)))?
+    };
+
+    let mut received = resumed_from;
+    on_progress(Progress {
+        resumed_from,
+        received,
+        total,
+    });
+    while let Some(chunk) = response.chunk().await.map_err(network)? {
+        file.write_all(&chunk)
+            .await
+            .map_err(|e| AttemptError::Fatal(io_error(staging, e)))?;
+        received += chunk.len() as u64;
+        on_progress(Progress {
+            resumed_from,
+            received,
+            total,
+        });
+    }
+    file.flush()
+        .await
+        .map_err(|e| AttemptError::Fatal(io_error(staging, e)))?;
+
+    match total {
+        Some(total) if received < total => Err(AttemptError::Retry(AppError::NetworkUnavailable {
+            details: format!("Connection closed after {} of {} bytes", received, total),
+        })),
+        _ => Ok(()),
+    }
+}
+
+/// Platform entry of the manifest that downloads `download_url`. Works for
+/// static (`platforms`) and dynamic manifests.
+pub fn manifest_entry<'a>(
+    manifest: &'a serde_json::Value,
+    download_url: &str,
+) -> Option<&'a serde_json::Value> {
+    let matches =
+        |entry: &serde_json::Value| entry.get("url").and_then(|u| u.as_str()) == Some(download_url);
+    match manifest.get("platforms").and_then(|p| p.as_object()) {
+        Some(platforms) => platforms.values().find(|entry| matches(entry)),
+        None if matches(manifest) => Some(manifest),
+        None => None,
+    }
+}
+
+/// SHA-256 listed in the manifest for the platform entry downloading
+/// `download_url`, if any.
+pub fn manifest_sha256(manifest: &serde_json::Value, download_url: &str) -> Option<String> {
+    let entry = manifest_entry(manifest, download_url)?;
+    entry.get("sha256")?.as_str().map(str::to_string)
+}
+
+fn decode_base64(value: &str) -> Result<String, AppError> {
+    let invalid = |details: String| AppError::UpdateSignatureInvalid { details };
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(value)
+        .map_err(|e| invalid(e.to_string()))?;
+    String::from_utf8(bytes).map_err(|e| invalid(e.to_string()))
+}
+
+/// Checks downloaded bytes against the release's minisign `signature`
+/// (base64, as in `latest.json`) and, if the manifest lists one, its
+/// SHA-256.
+pub fn verify(
+    data: &[u8],
+    signature: &str,
+    pubkey: &str,
+    sha256: Option<&str>,
+) -> Result<(), AppError> {
+    if let Some(expected) = sha256 {
+        let actual = format!("{:x}", Sha256::digest(data));
+        if !actual.eq_ignore_ascii_case(expected.trim()) {
+            return Err(AppError::UpdateChecksumMismatch {
+                details: format!("expected {}, got {}", expected, actual),
+            });
+        }
+    }
+
+    let invalid = |e: minisign_verify::Error| AppError::UpdateSignatureInvalid {
+        details: e.to_string(),
+    };
+    let public_key = PublicKey::decode(&decode_base64(pubkey)?).map_err(invalid)?;
+    let signature = Signature::decode(&decode_base64(signature)?).map_err(invalid)?;
+    public_key.verify(data, &signature, true).map_err(invalid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{signed_payload, TempDir, PUBKEY, SIGNATURE};
+    use std::sync::{Arc, Mutex};
+    use tokio::io::AsyncReadExt;
+    use tokio::net::TcpListener;
+
+    const SHA256: &str = "cd2df694e424bc7968cc37f47751019e5ca0cd1bdf2e479ea537c3a1c32ee1aa";
+
+    /// Stand-in for the release host: serves one file with `ETag` and
+    /// `Range` support over plain HTTP/1.1.
+    #[derive(Clone)]
+    struct Server {
+        body: Arc<Vec<u8>>,
+        etag: &'static str,
+        honor_range: bool,
+        /// Close the first response after this many body bytes.
+        drop_after: Option<usize>,
+        status: u16,
+        /// `Range` header of each request, in order.
+        ranges: Arc<Mutex<Vec<Option<String>>>>,
+    }
+
+    impl Server {
+        fn new() -> Self {
+            Self {
+                body: Arc::new(signed_payload()),
+                etag: "\"v2\"",
+                honor_range: true,
+                drop_after: None,
+                status: 200,
+                ranges: Arc::default(),
+            }
+        }
+
+        async fn start(self) -> (Url, Self) {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let url = format!("http://{}/app.msi", listener.local_addr().unwrap());
+            let server = self.clone();
+            tokio::spawn(async move {
+                loop {
+                    let (mut stream, _) = listener.accept().await.unwrap();
+                    let server = server.clone();
+                    tokio::spawn(async move {
+                        let mut request = Vec::new();
+                        let mut buf = [0u8; 1024];
+                        while !request.ends_with(b"\r\n\r\n") {
+                            let n = stream.read(&mut buf).await.unwrap();
+                            if n == 0 {
+                                return;
+                            }
+                            request.extend_from_slice(&buf[..n]);
+                        }
+                        let response = server.respond(&String::from_utf8_lossy(&request));
+                        let _ = stream.write_all(&response).await;
+                        let _ = stream.shutdown().await;
+                    });
+                }
+            });
+            (url.parse().unwrap(), self)
+        }
+
+        fn respond(&self, request: &str) -> Vec<u8> {
+            let header = |name: &str| {
+                request.lines().find_map(|line| {
+                    let (key, value) = line.split_once(':')?;
+                    key.eq_ignore_ascii_case(name)
+                        .then(|| value.trim().to_string())
+                })
+            };
+            let range = header("range");
+            let first = {
+                let mut ranges = self.ranges.lock().unwrap();
+                ranges.push(range.clone());
+                ranges.len() == 1
+            };
+            if self.status != 200 {
+                return format!(
+                    "HTTP/1.1 {} Error\r\nContent-Length: 0\r\n\r\n",
+                    self.status
+                )
+                .into_bytes();
+            }
+
+            let len = self.body.len();
+            let start = range
+                .filter(|_| self.honor_range)
+                .filter(|_| header("if-range").map_or(true, |v| v == self.etag))
+                .and_then(|r| {
+                    r.strip_prefix("bytes=")?
+                        .strip_suffix('-')?
+                        .parse::<usize>()
+                        .ok()
+                });
+            let (head, body) = match start {
+                Some(start) if start >= len => {
+                    return b"HTTP/1.1 416 Range Not Satisfiable\r\nContent-Length: 0\r\n\r\n"
+                        .to_vec();
+                }
+                Some(start) => (
+                    format!(
+                        "HTTP/1.1 206 Partial Content\r\nContent-Range: bytes {}-{}/{}\r\n",
+                        start,
+                        len - 1,
+                        len
+                    ),
+                    &self.body[start..],
+                ),
+                None => ("HTTP/1.1 200 OK\r\n".to_string(), &self.body[..]),
+            };
+            let mut response = format!(
+                "{}ETag: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                head,
+                self.etag,
+                body.len()
+            )
+            .into_bytes();
+            let sent = match self.drop_after {
+                Some(n) if first => &body[..n],
+                _ => body,
+            };
+            response.extend_from_slice(sent);
+            response
+        }
+
+        fn ranges(&self) -> Vec<Option<String>> {
+            self.ranges.lock().unwrap().clone()
+        }
+    }
+
+    fn staging() -> (TempDir, PathBuf) {
+        let dir = TempDir::new("update-download-test");
+        let path = dir.0.join("updates").join("1.0.0.part");
+        (dir, path)
+    }
+
+    /// Writes the first `len` bytes as if an earlier session was cut off.
+    async fn stage(staging: &Path, url: &Url, etag: &str, len: usize) {
+        fs::create_dir_all(staging.parent().unwrap()).await.unwrap();
+        fs::write(staging, &signed_payload()[..len]).await.unwrap();
+        let validator = Validator {
+            url: url.to_string(),
+            etag: Some(etag.to_string()),
+            last_modified: None,
+        };
+        fs::write(
+            validator_path(staging),
+            serde_json::to_string(&validator).unwrap(),
+        )
+        .await
+        .unwrap();
+    }
+
+    async fn download(url: &Url, staging: &Path) -> Result<(Vec<u8>, Vec<Progress>), AppError> {
+        let mut progress = Vec::new();
+        let bytes = fetch(
+            &reqwest::Client::new(),
+            url,
+            &HeaderMap::new(),
+            staging,
+            |p| progress.push(p),
+        )
+        .await?;
+        Ok((bytes, progress))
+    }
+
+    #[tokio::test]
+    async fn downloads_whole_file() {
+        let (url, server) = Server::new().start().await;
+        let (_dir, staging) = staging();
+
+        let (bytes, progress) = download(&url, &staging).await.unwrap();
+
+        assert_eq!(bytes, signed_payload());
+        assert_eq!(server.ranges(), vec![None]);
+        let last = progress.last().unwrap();
+        assert_eq!(last.received, 100_000);
+        assert_eq!(last.total, Some(100_000));
+    }
+
+    #[tokio::test]
+    async fn resumes_after_connection_drops() {
+        let (url, server) = Server {
+            drop_after: Some(30_000),
+            ..Server::new()
+        }
+        .start()
+        .await;
+        let (_dir, staging) = staging();
+
+        let (bytes, progress) = download(&url, &staging).await.unwrap();
+
+        assert_eq!(bytes, signed_payload());
+        assert_eq!(
+            server.ranges(),
+            vec![None, Some("bytes=30000-".to_string())]
+        );
+        assert!(progress.iter().any(|p| p.resumed_from == 30_000));
+    }
+
+    #[tokio::test]
+    async fn resumes_download_from_earlier_session() {
+        let (url, server) = Server::new().start().await;
+        let (_dir, staging) = staging();
+        stage(&staging, &url, "\"v2\"", 60_000).await;
+
+        let (bytes, progress) = download(&url, &staging).await.unwrap();
+
+        assert_eq!(bytes, signed_payload());
+        assert_eq!(server.ranges(), vec![Some("bytes=60000-".to_string())]);
+        assert_eq!(progress[0].resumed_from, 60_000);
+    }
+
+    #[tokio::test]
+    async fn restarts_when_file_changed_on_server() {
+        let (url, server) = Server::new().start().await;
+        let (_dir, staging) = staging();
+        // Staged from an older build; If-Range no longer matches
+        stage(&staging, &url, "\"v1\"", 60_000).await;
+
+        let (bytes, _) = download(&url, &staging).await.unwrap();
+
+        assert_eq!(bytes, signed_payload());
+        assert_eq!(server.ranges(), vec![Some("bytes=60000-".to_string())]);
+    }
+
+    #[tokio::test]
+    async fn restarts_when_server_ignores_range() {
+        let (url, _server) = Server {
+            honor_range: false,
+            ..Server::new()
+        }
+        .start()
+        .await;
+        let (_dir, staging) = staging();
+        stage(&staging, &url, "\"v2\"", 60_000).await;
+
+        let (bytes, _) = download(&url, &staging).await.unwrap();
+
+        assert_eq!(bytes, signed_payload());
+    }
+
+    #[tokio::test]
+    async fn ignores_staged_bytes_from_another_url() {
+        let (url, server) = Server::new().start().await;
+        let (_dir, staging) = staging();
+        let other: Url = "http://127.0.0.1:1/old.msi".parse().unwrap();
+        stage(&staging, &other, "\"v2\"", 60_000).await;
+
+        let (bytes, _) = download(&url, &staging).await.unwrap();
+
+        assert_eq!(bytes, signed_payload());
+        assert_eq!(server.ranges(), vec![None]);
+    }
+
+    #[tokio::test]
+    async fn starts_over_when_staged_file_is_too_long() {
+        let (url, server) = Server::new().start().await;
+        let (_dir, staging) = staging();
+        stage(&staging, &url, "\"v2\"", 100_000).await;
+
+        let (bytes, _) = download(&url, &staging).await.unwrap();
+
+        assert_eq!(bytes, signed_payload());
+        assert_eq!(
+            server.ranges(),
+            vec![Some("bytes=100000-".to_string()), None]
+        );
+    }
+
+    #[tokio::test]
+    async fn fails_without_retry_on_http_error() {
+        let (url, server) = Server {
+            status: 404,
+            ..Server::new()
+        }
+        .start()
+        .await;
+        let (_dir, staging) = staging();
+
+        let error = download(&url, &staging).await.unwrap_err();
+
+        assert_eq!(error.kind(), "UpdateFailed");
+        assert_eq!(server.ranges().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn removes_other_staged_releases() {
+        let (url, _server) = Server::new().start().await;
+        let (_dir, staging) = staging();
+        let old = staging.with_file_name("0.9.0.part");
+        fs::create_dir_all(staging.parent().unwrap()).await.unwrap();
+        fs::write(&old, b"old").await.unwrap();
+
+        download(&url, &staging).await.unwrap();
+
+        assert!(!old.exists());
+    }
+
+    #[test]
+    fn verify_accepts_signed_payload() {
+        verify(&signed_payload(), SIGNATURE, PUBKEY, None).unwrap();
+        verify(&signed_payload(), SIGNATURE, PUBKEY, Some(SHA256)).unwrap();
+        verify(
+            &signed_payload(),
+            SIGNATURE,
+            PUBKEY,
+            Some(&SHA256.to_uppercase()),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn verify_rejects_tampered_payload() {
+        let mut data = signed_payload();
+        data[1234] ^= 0xff;
+        let error = verify(&data, SIGNATURE, PUBKEY, None).unwrap_err();
+        assert_eq!(error.kind(), "UpdateSignatureInvalid");
+    }
+
+    #[test]
+    fn verify_rejects_checksum_mismatch() {
+        let wrong = "0".repeat(64);
+        let error = verify(&signed_payload(), SIGNATURE, PUBKEY, Some(&wrong)).unwrap_err();
+        assert_eq!(error.kind(), "UpdateChecksumMismatch");
+    }
+
+    #[test]
+    fn verify_rejects_malformed_signature() {
+        let error = verify(&signed_payload(), "not base64!", PUBKEY, None).unwrap_err();
+        assert_eq!(error.kind(), "UpdateSignatureInvalid");
+    }
+
+    #[test]
+    fn finds_sha256_in_manifest() {
+        let url = "https://example.com/app_1.0.0_x64.msi";
+        let manifest = serde_json::json!({
+            "version": "1.0.0",
+            "platforms": {
+                "darwin-aarch64": { "url": "https://example.com/app.app.tar.gz", "sha256": "aa" },
+                "windows-x86_64": { "url": url, "signature": "", "sha256": "bb" }
+            }
+        });
+        assert_eq!(manifest_sha256(&manifest, url).as_deref(), Some("bb"));
+        assert_eq!(
+            manifest_sha256(&manifest, "https://example.com/other"),
+            None
+        );
+
+        let dynamic = serde_json::json!({ "version": "1.0.0", "url": url, "sha256": "cc" });
+        assert_eq!(manifest_sha256(&dynamic, url).as_deref(), Some("cc"));
+
+        let without = serde_json::json!({ "version": "1.0.0", "url": url });
+        assert_eq!(manifest_sha256(&without, url), None);
+    }
+}
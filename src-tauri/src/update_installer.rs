@@ -0,0 +1,100 @@
+use crate::error::AppError;
+use tauri::AppHandle;
+
+/// Runs a saved installer directly, the way the updater plugin would. The
+/// plugin can only install through an `Update` from a fresh server check,
+/// which a rollback must not depend on.
+///
+/// On Windows the installer replaces the app, so this exits it. Elsewhere
+/// the caller should relaunch afterwards.
+pub fn install(app_handle: &AppHandle, version: &str, bytes: &[u8]) -> Result<(), AppError> {
+    install_inner(app_handle, version, bytes)
+}
+
+fn failed(details: String) -> AppError {
+    AppError::UpdateFailed { details }
+}
+
+/// Start of every MSI (an OLE compound file).
+#[cfg(windows)]
+const MSI_MAGIC: &[u8] = &[0xD0, 0xCF, 0x11, 0xE0, 0xA1, 0xB1, 0x1A, 0xE1];
+
+#[cfg(windows)]
+fn install_inner(app_handle: &AppHandle, version: &str, bytes: &[u8]) -> Result<(), AppError> {
+    use std::os::windows::process::CommandExt;
+    use std::process::Command;
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+
+    let msi = bytes.starts_with(MSI_MAGIC);
+    if !msi && !bytes.starts_with(b"MZ") {
+        return Err(failed(format!(
+            "Installer for {} is neither an MSI nor an NSIS setup",
+            version
+        )));
+    }
+    let app_name = &app_handle.package_info().name;
+    let dir = std::env::temp_dir().join(format!("{}-{}-rollback", app_name, version));
+    let path = dir.join(format!(
+        "{}-{}-installer.{}",
+        app_name,
+        version,
+        if msi { "msi" } else { "exe" }
+    ));
+    std::fs::create_dir_all(&dir)
+        .and_then(|_| std::fs::write(&path, bytes))
+        .map_err(|e| failed(format!("{}: {}", path.display(), e)))?;
+
+    // Same arguments as the plugin's default passive install mode
+    let mut command = if msi {
+        let system_root = std::env::var("SYSTEMROOT").unwrap_or_else(|_| "C:\\Windows".into());
+        let mut command = Command::new(format!("{}\\System32\\msiexec.exe", system_root));
+        command
+            .arg("/i")
+            .arg(&path)
+            .args(["/passive", "/promptrestart", "AUTOLAUNCHAPP=True"])
+            .creation_flags(CREATE_NO_WINDOW);
+        command
+    } else {
+        let mut command = Command::new(&path);
+        command.args(["/P", "/R", "/UPDATE"]);
+        command
+    };
+    command
+        .spawn()
+        .map_err(|e| failed(format!("Failed to start {}: {}", path.display(), e)))?;
+    log::info!("Started installer for {}, exiting", version);
+    std::process::exit(0);
+}
+
+/// Replaces the running AppImage, as the plugin does.
+#[cfg(target_os = "linux")]
+fn install_inner(_app_handle: &AppHandle, version: &str, bytes: &[u8]) -> Result<(), AppError> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let appimage = std::env::var_os("APPIMAGE")
+        .map(std::path::PathBuf::from)
+        .ok_or_else(|| failed("Only AppImage installs can be rolled back".to_string()))?;
+    if !bytes.starts_with(b"\x7fELF") {
+        return Err(failed(format!(
+            "Installer for {} is not an AppImage",
+            version
+        )));
+    }
+    let mut temp = appimage.clone().into_os_string();
+    temp.push(".rollback");
+    let temp = std::path::PathBuf::from(temp);
+    std::fs::write(&temp, bytes)
+        .and_then(|_| std::fs::set_permissions(&temp, std::fs::Permissions::from_mode(0o755)))
+        .and_then(|_| std::fs::rename(&temp, &appimage))
+        .map_err(|e| {
+            let _ = std::fs::remove_file(&temp);
+            failed(format!("{}: {}", appimage.display(), e))
+        })
+}
+
+#[cfg(not(any(windows, target_os = "linux")))]
+fn install_inner(_app_handle: &AppHandle, _version: &str, _bytes: &[u8]) -> Result<(), AppError> {
+    Err(failed(
+        "Rolling back is not supported on this platform".to_string(),
+    ))
+}
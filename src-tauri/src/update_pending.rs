@@ -0,0 +1,96 @@
+use crate::error::AppError;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager};
+use ts_rs::TS;
+
+/// Directory in app data holding the downloaded installer.
+const PENDING_DIR: &str = "updates";
+/// Describes the installer in `PENDING_DIR`.
+const PENDING_FILE: &str = "pending.json";
+
+/// A verified update waiting to be installed. Kept on disk so it survives
+/// restarts until it is installed or superseded.
+#[derive(Clone, Debug, Deserialize, Serialize, TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct PendingUpdate {
+    pub version: String,
+    /// Install when the app quits instead of waiting for the user.
+    pub install_on_exit: bool,
+}
+
+fn dir(app_handle: &AppHandle) -> Result<PathBuf, AppError> {
+    Ok(app_handle.path().app_data_dir()?.join(PENDING_DIR))
+}
+
+fn installer_path(dir: &Path, version: &str) -> PathBuf {
+    dir.join(format!("update-{}.bin", version))
+}
+
+fn io_error(path: &Path, e: std::io::Error) -> AppError {
+    AppError::Internal(format!("{}: {}", path.display(), e))
+}
+
+fn write(app_handle: &AppHandle, pending: &PendingUpdate) -> Result<(), AppError> {
+    let path = dir(app_handle)?.join(PENDING_FILE);
+    let json =
+        serde_json::to_string_pretty(pending).map_err(|e| AppError::Internal(e.to_string()))?;
+    fs::write(&path, json).map_err(|e| io_error(&path, e))
+}
+
+/// The update on disk, if any.
+pub fn load(app_handle: &AppHandle) -> Option<PendingUpdate> {
+    let path = dir(app_handle).ok()?.join(PENDING_FILE);
+    let contents = fs::read_to_string(path).ok()?;
+    serde_json::from_str(&contents).ok()
+}
+
+/// Stores verified installer bytes for `version`, replacing any older
+/// pending update.
+pub fn save(
+    app_handle: &AppHandle,
+    version: &str,
+    bytes: &[u8],
+) -> Result<PendingUpdate, AppError> {
+    clear(app_handle);
+    let dir = dir(app_handle)?;
+    fs::create_dir_all(&dir).map_err(|e| io_error(&dir, e))?;
+    let pending = PendingUpdate {
+        version: version.to_string(),
+        install_on_exit: false,
+    };
+    let path = installer_path(&dir, version);
+    fs::write(&path, bytes).map_err(|e| io_error(&path, e))?;
+    write(app_handle, &pending)?;
+    Ok(pending)
+}
+
+pub fn set_install_on_exit(
+    app_handle: &AppHandle,
+    enabled: bool,
+) -> Result<PendingUpdate, AppError> {
+    let mut pending = load(app_handle).ok_or(AppError::NoUpdateAvailable)?;
+    pending.install_on_exit = enabled;
+    write(app_handle, &pending)?;
+    Ok(pending)
+}
+
+/// Installer bytes of the pending update.
+pub fn read(app_handle: &AppHandle, pending: &PendingUpdate) -> Result<Vec<u8>, AppError> {
+    let path = installer_path(&dir(app_handle)?, &pending.version);
+    fs::read(&path).map_err(|e| io_error(&path, e))
+}
+
+/// Forgets the pending update and deletes its installer.
+pub fn clear(app_handle: &AppHandle) {
+    let Ok(dir) = dir(app_handle) else {
+        return;
+    };
+    if let Err(e) = fs::remove_dir_all(&dir) {
+        if e.kind() != std::io::ErrorKind::NotFound {
+            log::error!("Failed to remove {}: {}", dir.display(), e);
+        }
+    }
+}
@@ -0,0 +1,412 @@
+use crate::error::AppError;
+use crate::update_download;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager, Url};
+
+/// Directory in app data holding the installers a rollback can go back to.
+const ROLLBACK_DIR: &str = "rollback";
+/// Describes the installers in `ROLLBACK_DIR`.
+const STATE_FILE: &str = "state.json";
+
+/// An installer kept after installing it, with its signature so it can be
+/// verified again before reuse.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SavedRelease {
+    pub version: String,
+    pub signature: String,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct RollbackState {
+    /// Release the updater installed last.
+    pub installed: Option<SavedRelease>,
+    /// Release `installed` replaced; what a rollback reinstalls.
+    pub previous: Option<SavedRelease>,
+    /// Installer of the running version when the updater did not install it
+    /// (first update, manual install), fetched before updating away from it.
+    pub running: Option<SavedRelease>,
+    /// `installed` has launched successfully at least once.
+    pub healthy: bool,
+    /// Launches of `installed` that never reported back healthy.
+    pub failed_launches: u32,
+    /// Release rolled back from, which is not offered again.
+    pub rejected: Option<String>,
+}
+
+/// An earlier release the manifest lists under `releases` in the platform
+/// entry of the update, keyed by version:
+///
+/// `"releases": {"0.0.1": {"url": "...", "signature": "...", "sha256": "..."}}`
+///
+/// `sha256` is optional, as for the update itself.
+#[derive(Debug, PartialEq)]
+pub struct ListedRelease {
+    pub url: Url,
+    pub signature: String,
+    pub sha256: Option<String>,
+}
+
+/// Looks up `version` in the `releases` of the manifest entry that
+/// downloads `download_url`.
+pub fn listed_release(
+    manifest: &serde_json::Value,
+    download_url: &str,
+    version: &str,
+) -> Option<ListedRelease> {
+    let release = update_download::manifest_entry(manifest, download_url)?
+        .get("releases")?
+        .get(version)?;
+    let field = |name: &str| release.get(name)?.as_str().map(str::to_string);
+    Some(ListedRelease {
+        url: Url::parse(&field("url")?).ok()?,
+        signature: field("signature")?,
+        sha256: field("sha256"),
+    })
+}
+
+fn io_error(path: &Path, e: std::io::Error) -> AppError {
+    AppError::Internal(format!("{}: {}", path.display(), e))
+}
+
+/// Saved installers and rollback state for the running version.
+pub struct Rollback {
+    dir: PathBuf,
+    running: String,
+}
+
+impl Rollback {
+    pub fn open(app_handle: &AppHandle) -> Result<Self, AppError> {
+        Ok(Self::at(
+            app_handle.path().app_data_dir()?.join(ROLLBACK_DIR),
+            &app_handle.package_info().version.to_string(),
+        ))
+    }
+
+    fn at(dir: PathBuf, running: &str) -> Self {
+        Self {
+            dir,
+            running: running.to_string(),
+        }
+    }
+
+    pub fn running(&self) -> &str {
+        &self.running
+    }
+
+    fn installer_path(&self, version: &str) -> PathBuf {
+        self.dir.join(format!("{}.bin", version))
+    }
+
+    pub fn has_installer(&self, version: &str) -> bool {
+        self.installer_path(version).is_file()
+    }
+
+    pub fn load(&self) -> RollbackState {
+        fs::read_to_string(self.dir.join(STATE_FILE))
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, state: &RollbackState) -> Result<(), AppError> {
+        fs::create_dir_all(&self.dir).map_err(|e| io_error(&self.dir, e))?;
+        let path = self.dir.join(STATE_FILE);
+        let json =
+            serde_json::to_string_pretty(state).map_err(|e| AppError::Internal(e.to_string()))?;
+        fs::write(&path, json).map_err(|e| io_error(&path, e))?;
+        self.remove_unused(state);
+        Ok(())
+    }
+
+    /// Deletes installers `state` no longer refers to.
+    fn remove_unused(&self, state: &RollbackState) {
+        let keep: Vec<PathBuf> = [&state.installed, &state.previous, &state.running]
+            .into_iter()
+            .flatten()
+            .map(|release| self.installer_path(&release.version))
+            .collect();
+        let Ok(entries) = fs::read_dir(&self.dir) else {
+            return;
+        };
+        for path in entries.flatten().map(|entry| entry.path()) {
+            if path.extension().is_some_and(|ext| ext == "bin") && !keep.contains(&path) {
+                if let Err(e) = fs::remove_file(&path) {
+                    log::error!("Failed to remove {}: {}", path.display(), e);
+                }
+            }
+        }
+    }
+
+    fn write_installer(&self, version: &str, bytes: &[u8]) -> Result<(), AppError> {
+        fs::create_dir_all(&self.dir).map_err(|e| io_error(&self.dir, e))?;
+        let path = self.installer_path(version);
+        fs::write(&path, bytes).map_err(|e| io_error(&path, e))
+    }
+
+    /// Keeps the verified installer of the running version, so the next
+    /// install can be rolled back even though the updater did not install
+    /// this version.
+    pub fn keep_running(&self, signature: &str, bytes: &[u8]) -> Result<(), AppError> {
+        self.write_installer(&self.running, bytes)?;
+        let mut state = self.load();
+        state.running = Some(SavedRelease {
+            version: self.running.clone(),
+            signature: signature.to_string(),
+        });
+        self.save(&state)
+    }
+
+    /// Keeps the installer of a release about to be installed. The running
+    /// version becomes the rollback target whenever its installer is saved;
+    /// otherwise the existing target is kept.
+    pub fn record_install(
+        &self,
+        version: &str,
+        signature: &str,
+        bytes: &[u8],
+    ) -> Result<(), AppError> {
+        let state = self.load();
+        let previous = [state.installed, state.running]
+            .into_iter()
+            .flatten()
+            .find(|release| release.version == self.running && self.has_installer(&release.version))
+            .or(state.previous)
+            .filter(|previous| previous.version != version);
+
+        self.write_installer(version, bytes)?;
+        self.save(&RollbackState {
+            installed: Some(SavedRelease {
+                version: version.to_string(),
+                signature: signature.to_string(),
+            }),
+            previous,
+            running: None,
+            healthy: false,
+            failed_launches: 0,
+            rejected: state.rejected.filter(|rejected| rejected != version),
+        })
+    }
+
+    /// Counts a launch of the running version while it is still on probation
+    /// after being installed. Returns how many earlier launches never
+    /// reported back healthy, or `None` if it is not on probation.
+    pub fn begin_launch(&self) -> Option<u32> {
+        let mut state = self.load();
+        if state.healthy || state.installed.as_ref()?.version != self.running {
+            return None;
+        }
+        let failed = state.failed_launches;
+        state.failed_launches += 1;
+        self.save(&state)
+            .unwrap_or_else(|e| log::error!("Failed to record launch of {}: {}", self.running, e));
+        Some(failed)
+    }
+
+    /// Marks the running version good if it is the one the updater installed.
+    pub fn mark_healthy(&self) -> Result<(), AppError> {
+        let mut state = self.load();
+        if state.healthy || state.installed.as_ref().map(|i| &i.version) != Some(&self.running) {
+            return Ok(());
+        }
+        state.healthy = true;
+        state.failed_launches = 0;
+        self.save(&state)
+    }
+
+    /// Installer bytes of a saved release.
+    pub fn read(&self, release: &SavedRelease) -> Result<Vec<u8>, AppError> {
+        let path = self.installer_path(&release.version);
+        fs::read(&path).map_err(|e| io_error(&path, e))
+    }
+
+    /// Reinstalls the previous release from its saved installer with
+    /// `install`, and stops offering the running one. Nothing is fetched, so
+    /// this works offline. The state is restored if `install` fails.
+    pub fn roll_back(
+        &self,
+        pubkey: &str,
+        install: impl FnOnce(&SavedRelease, &[u8]) -> Result<(), AppError>,
+    ) -> Result<SavedRelease, AppError> {
+        let state = self.load();
+        let previous = state
+            .previous
+            .clone()
+            .ok_or(AppError::NoRollbackAvailable)?;
+        let bytes = self.read(&previous)?;
+        update_download::verify(&bytes, &previous.signature, pubkey, None)?;
+
+        // Saved first, since the Windows installer exits the app
+        self.save(&RollbackState {
+            installed: Some(previous.clone()),
+            previous: None,
+            running: None,
+            healthy: true,
+            failed_launches: 0,
+            rejected: Some(self.running.clone()),
+        })?;
+        log::info!("Rolling back to {}", previous.version);
+        if let Err(e) = install(&previous, &bytes) {
+            self.save(&state)?;
+            return Err(e);
+        }
+        Ok(previous)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{signed_payload, TempDir, PUBKEY, SIGNATURE};
+
+    fn release(version: &str, signature: &str) -> Option<SavedRelease> {
+        Some(SavedRelease {
+            version: version.to_string(),
+            signature: signature.to_string(),
+        })
+    }
+
+    #[test]
+    fn first_update_keeps_the_running_installer() {
+        let dir = TempDir::new("rollback-test");
+        let rollback = Rollback::at(dir.0.clone(), "0.0.1");
+        rollback.keep_running(SIGNATURE, &signed_payload()).unwrap();
+        rollback.record_install("0.0.2", "sig-2", b"new").unwrap();
+
+        let state = rollback.load();
+        assert_eq!(state.installed, release("0.0.2", "sig-2"));
+        assert_eq!(state.previous, release("0.0.1", SIGNATURE));
+        assert_eq!(state.running, None);
+        assert!(rollback.has_installer("0.0.1"));
+    }
+
+    #[test]
+    fn manual_install_replaces_the_rollback_target() {
+        let dir = TempDir::new("rollback-test");
+        Rollback::at(dir.0.clone(), "0.0.1")
+            .record_install("0.0.2", "sig-2", b"two")
+            .unwrap();
+
+        // 0.0.3 was then installed by hand, and is now updating itself
+        let rollback = Rollback::at(dir.0.clone(), "0.0.3");
+        rollback.keep_running("sig-3", b"three").unwrap();
+        rollback.record_install("0.0.4", "sig-4", b"four").unwrap();
+
+        assert_eq!(rollback.load().previous, release("0.0.3", "sig-3"));
+        assert!(!rollback.has_installer("0.0.2"));
+    }
+
+    #[test]
+    fn updater_installed_version_is_kept_before_it_is_marked_healthy() {
+        let dir = TempDir::new("rollback-test");
+        Rollback::at(dir.0.clone(), "0.0.1")
+            .record_install("0.0.2", "sig-2", b"two")
+            .unwrap();
+
+        let rollback = Rollback::at(dir.0.clone(), "0.0.2");
+        rollback.record_install("0.0.3", "sig-3", b"three").unwrap();
+
+        assert_eq!(rollback.load().previous, release("0.0.2", "sig-2"));
+    }
+
+    #[test]
+    fn rollback_installs_the_saved_installer_without_the_server() {
+        let dir = TempDir::new("rollback-test");
+        Rollback::at(dir.0.clone(), "0.0.1")
+            .keep_running(SIGNATURE, &signed_payload())
+            .unwrap();
+        Rollback::at(dir.0.clone(), "0.0.1")
+            .record_install("0.0.2", "sig-2", b"new")
+            .unwrap();
+
+        let rollback = Rollback::at(dir.0.clone(), "0.0.2");
+        let mut installed = None;
+        let previous = rollback
+            .roll_back(PUBKEY, |release, bytes| {
+                installed = Some((release.version.clone(), bytes.to_vec()));
+                Ok(())
+            })
+            .unwrap();
+
+        assert_eq!(previous.version, "0.0.1");
+        assert_eq!(installed, Some(("0.0.1".to_string(), signed_payload())));
+        let state = rollback.load();
+        assert_eq!(state.installed, release("0.0.1", SIGNATURE));
+        assert_eq!(state.previous, None);
+        assert_eq!(state.rejected.as_deref(), Some("0.0.2"));
+    }
+
+    #[test]
+    fn failed_rollback_restores_the_state() {
+        let dir = TempDir::new("rollback-test");
+        Rollback::at(dir.0.clone(), "0.0.1")
+            .keep_running(SIGNATURE, &signed_payload())
+            .unwrap();
+        Rollback::at(dir.0.clone(), "0.0.1")
+            .record_install("0.0.2", "sig-2", b"new")
+            .unwrap();
+
+        let rollback = Rollback::at(dir.0.clone(), "0.0.2");
+        let result = rollback.roll_back(PUBKEY, |_, _| Err(AppError::Cancelled));
+
+        assert!(matches!(result, Err(AppError::Cancelled)));
+        let state = rollback.load();
+        assert_eq!(state.installed, release("0.0.2", "sig-2"));
+        assert_eq!(state.previous, release("0.0.1", SIGNATURE));
+        assert!(rollback.has_installer("0.0.1"));
+    }
+
+    #[test]
+    fn rollback_refuses_a_tampered_installer() {
+        let dir = TempDir::new("rollback-test");
+        let running = Rollback::at(dir.0.clone(), "0.0.1");
+        running
+            .keep_running(SIGNATURE, b"not what was signed")
+            .unwrap();
+        running.record_install("0.0.2", "sig-2", b"new").unwrap();
+
+        let result = Rollback::at(dir.0.clone(), "0.0.2").roll_back(PUBKEY, |_, _| {
+            panic!("a tampered installer must not run");
+        });
+        assert!(matches!(
+            result,
+            Err(AppError::UpdateSignatureInvalid { .. })
+        ));
+    }
+
+    #[test]
+    fn listed_release_comes_from_the_update_entry() {
+        let url = "https://example.com/0.0.2/app_0.0.2_x64.msi";
+        let manifest = serde_json::json!({
+            "version": "0.0.2",
+            "platforms": {
+                "windows-x86_64": {
+                    "url": url,
+                    "signature": "new",
+                    "releases": {
+                        "0.0.1": {
+                            "url": "https://example.com/old/app_0.0.1_x64.msi",
+                            "signature": "old",
+                        }
+                    }
+                }
+            }
+        });
+        assert_eq!(
+            listed_release(&manifest, url, "0.0.1"),
+            Some(ListedRelease {
+                url: Url::parse("https://example.com/old/app_0.0.1_x64.msi").unwrap(),
+                signature: "old".to_string(),
+                sha256: None,
+            })
+        );
+        assert_eq!(listed_release(&manifest, url, "0.0.0"), None);
+        assert_eq!(
+            listed_release(&manifest, "https://example.com/other", "0.0.1"),
+            None
+        );
+    }
+}
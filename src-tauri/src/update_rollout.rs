@@ -0,0 +1,136 @@
+use crate::error::AppError;
+use sha2::{Digest, Sha256};
+use std::fs;
+use tauri::{AppHandle, Manager};
+
+/// File in app data holding this install's id.
+const INSTALL_ID_FILE: &str = "install-id";
+
+/// Random id for this installation, created on first use. Allowlists in
+/// `latest.json` name installs by this id.
+pub fn install_id(app_handle: &AppHandle) -> Result<String, AppError> {
+    let dir = app_handle.path().app_data_dir()?;
+    let path = dir.join(INSTALL_ID_FILE);
+    if let Ok(id) = fs::read_to_string(&path) {
+        let id = id.trim();
+        if !id.is_empty() {
+            return Ok(id.to_string());
+        }
+    }
+
+    let id: String = rand::random::<[u8; 16]>()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    fs::create_dir_all(&dir).map_err(|e| AppError::Internal(e.to_string()))?;
+    fs::write(&path, &id).map_err(|e| AppError::Internal(format!("{}: {}", path.display(), e)))?;
+    Ok(id)
+}
+
+/// Where this install falls in `version`'s rollout, from 0 to 99. Hashing
+/// with the version picks a different first cohort for every release, and
+/// raising the percentage only ever adds installs.
+fn bucket(install_id: &str, version: &str) -> u64 {
+    let digest = Sha256::digest(format!("{}:{}", install_id, version));
+    let mut head = [0; 8];
+    head.copy_from_slice(&digest[..8]);
+    u64::from_be_bytes(head) % 100
+}
+
+/// Whether the release in `manifest` is offered to this install.
+///
+/// `rollout` is the percentage of installs to offer it to, all of them if
+/// it is missing; setting it to 0 pauses the rollout. A `rollout` that is
+/// not a number holds the release back rather than offering it to all.
+/// Installs listed in `allowlist` get the release regardless.
+pub fn is_eligible(manifest: &serde_json::Value, version: &str, install_id: &str) -> bool {
+    let allowlisted = manifest
+        .get("allowlist")
+        .and_then(|ids| ids.as_array())
+        .is_some_and(|ids| ids.iter().any(|id| id.as_str() == Some(install_id)));
+    if allowlisted {
+        return true;
+    }
+    match manifest.get("rollout") {
+        None | Some(serde_json::Value::Null) => true,
+        Some(rollout) => match rollout.as_f64() {
+            Some(percent) => (bucket(install_id, version) as f64) < percent,
+            None => {
+                log::warn!(
+                    "Ignoring release {} with invalid rollout {}",
+                    version,
+                    rollout
+                );
+                false
+            }
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn ids() -> impl Iterator<Item = String> {
+        (0..1000).map(|i| format!("{:032x}", i))
+    }
+
+    fn eligible_share(manifest: &serde_json::Value) -> usize {
+        ids()
+            .filter(|id| is_eligible(manifest, "1.2.0", id))
+            .count()
+    }
+
+    #[test]
+    fn zero_percent_offers_to_nobody() {
+        assert_eq!(eligible_share(&json!({ "rollout": 0 })), 0);
+    }
+
+    #[test]
+    fn hundred_percent_offers_to_everybody() {
+        assert_eq!(eligible_share(&json!({ "rollout": 100 })), 1000);
+    }
+
+    #[test]
+    fn partial_rollout_offers_to_roughly_that_share() {
+        let share = eligible_share(&json!({ "rollout": 25 }));
+        assert!((150..350).contains(&share), "{} of 1000", share);
+    }
+
+    #[test]
+    fn bucket_is_stable_for_an_install() {
+        for id in ids().take(50) {
+            assert_eq!(bucket(&id, "1.2.0"), bucket(&id, "1.2.0"));
+            assert!(bucket(&id, "1.2.0") < 100);
+        }
+        // Raising the percentage never drops an install already included
+        for id in ids() {
+            let at_10 = is_eligible(&json!({ "rollout": 10 }), "1.2.0", &id);
+            let at_50 = is_eligible(&json!({ "rollout": 50 }), "1.2.0", &id);
+            assert!(!at_10 || at_50);
+        }
+    }
+
+    #[test]
+    fn allowlist_overrides_the_percentage() {
+        let manifest = json!({ "rollout": 0, "allowlist": ["tester", "other"] });
+        assert!(is_eligible(&manifest, "1.2.0", "tester"));
+        assert!(!is_eligible(&manifest, "1.2.0", "someone-else"));
+    }
+
+    #[test]
+    fn missing_rollout_offers_to_everybody() {
+        assert_eq!(eligible_share(&json!({ "version": "1.2.0" })), 1000);
+        assert_eq!(eligible_share(&json!({ "rollout": null })), 1000);
+    }
+
+    #[test]
+    fn malformed_rollout_offers_to_nobody() {
+        assert_eq!(eligible_share(&json!({ "rollout": "50" })), 0);
+        assert_eq!(eligible_share(&json!({ "rollout": [50] })), 0);
+        // A malformed allowlist is ignored
+        let manifest = json!({ "rollout": 0, "allowlist": "tester" });
+        assert!(!is_eligible(&manifest, "1.2.0", "tester"));
+    }
+}